@@ -0,0 +1,496 @@
+use std::fmt;
+
+use crate::instructions::*;
+use crate::symbols::*;
+use crate::util::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AddressingMode {
+	Simple,
+	Immediate,
+	Indirect,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TargetAddressing {
+	PcRelative,
+	BaseRelative,
+	Direct,
+}
+
+#[derive(Clone, Copy)]
+pub struct Addressing {
+	pub mode: AddressingMode,
+	pub target: TargetAddressing,
+	pub indexed: bool,
+}
+
+impl AddressingMode {
+	pub fn name(self) -> &'static str {
+		match self {
+			AddressingMode::Simple => "simple",
+			AddressingMode::Immediate => "immediate",
+			AddressingMode::Indirect => "indirect",
+		}
+	}
+}
+
+impl TargetAddressing {
+	pub fn name(self) -> &'static str {
+		match self {
+			TargetAddressing::PcRelative => "pc-relative",
+			TargetAddressing::BaseRelative => "base-relative",
+			TargetAddressing::Direct => "direct",
+		}
+	}
+}
+
+impl fmt::Display for Addressing {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} {}{}", self.target.name(), self.mode.name(), if self.indexed { " indexed" } else { "" })
+	}
+}
+
+pub struct AssembledLine {
+	pub line_number: usize,
+	pub source: String,
+	pub address: i32,
+	pub format: Option<i32>,
+	pub addressing: Option<Addressing>,
+	/// Promoted from format 3 to format 4 by relaxation
+	pub relaxed: bool,
+	pub code: Vec<u8>,
+}
+
+pub struct Modification {
+	pub line_number: usize,
+	pub address: i32,
+	pub half_bytes: i32,
+	/// '+' to add the symbol's address to the field, '-' to subtract it
+	pub sign: char,
+	pub symbol: String,
+}
+
+pub struct ObjectCode {
+	pub lines: Vec<AssembledLine>,
+	pub modifications: Vec<Modification>,
+	/// Pass 2 warnings, the object code is still usable
+	pub warnings: Vec<Diagnostic>,
+}
+
+pub fn assemble(symbol_table: &mut SymbolTable) -> AsmResult<ObjectCode> {
+	let mut object_code = ObjectCode {
+		lines: vec![],
+		modifications: vec![],
+		warnings: vec![],
+	};
+
+	// value B was last loaded with by an immediate LDB, used to check BASE directives
+	let mut loaded_base: Option<i32> = None;
+
+	for index in 0..symbol_table.lines.len() {
+		let line_number = index + 1;
+		let source_line = &symbol_table.lines[index];
+
+		let mut assembled_line = AssembledLine {
+			line_number,
+			source: source_line.source.clone(),
+			address: symbol_table.memory_locations[index],
+			format: None,
+			addressing: None,
+			relaxed: source_line.relaxed,
+			code: vec![],
+		};
+
+		let operation = source_line.operation.clone();
+		let operand = source_line.operand.clone();
+
+		if let Some(operation) = operation {
+			if is_instruction(&operation) {
+				assembled_line.format = Some(get_instruction_format(&operation));
+				(assembled_line.code, assembled_line.addressing) = get_instruction_code(symbol_table, line_number, &operation,
+				                                                                        operand.as_ref(), &mut object_code.modifications)?;
+
+				if operation.trim_start_matches("+") == "LDB" {
+					loaded_base = get_immediate_value(symbol_table, operand.as_ref());
+				}
+			} else if is_directive(&operation) {
+				assembled_line.code = get_directive_code(symbol_table, line_number, &operation, operand.as_ref(),
+				                                         &mut object_code.modifications)?;
+
+				if operation == "BASE" {
+					let base_location = symbol_table.get_symbol_location(operand.as_ref().unwrap());
+					if loaded_base != Some(base_location) {
+						object_code.warnings.push(Diagnostic::warning(line_number, format!("BASE {} is not preceded by an LDB #{} so B may not hold the base address!",
+						                                                                   operand.as_ref().unwrap(), operand.as_ref().unwrap())));
+					}
+				}
+			} else {
+				return Err(Diagnostic::error(line_number, "Could not parse line!"));
+			}
+		}
+
+		object_code.lines.push(assembled_line);
+	}
+
+	// an absolute program is loaded where it was assembled, so nothing needs relocating
+	if !symbol_table.is_relocatable() {
+		object_code.modifications.clear();
+	}
+
+	Ok(object_code)
+}
+
+fn get_instruction_code(symbol_table: &SymbolTable, line_number: usize,
+                        opcode: &str, operand: Option<&String>,
+                        modifications: &mut Vec<Modification>) -> AsmResult<(Vec<u8>, Option<Addressing>)> {
+	let current_memory_location = symbol_table.memory_locations.get(line_number - 1).unwrap();
+	// pass 1 already checked this unless the symbol table came from an intermediate file
+	check_operand(opcode, operand.map(|operand| operand.as_str()), symbol_table.source_format.sic, line_number)?;
+
+	let opcode_hex = get_instruction_hex(opcode);
+
+	let instruction_format = get_instruction_format(opcode);
+
+	let mut hash_symbol = false;
+	let mut at_symbol = false;
+	let mut x_index = false;
+
+	let mut operand = operand.cloned().unwrap_or_default();
+	let register_operand = operand.clone();
+
+	if operand.starts_with("#") {
+		hash_symbol = true;
+		operand = operand.as_str()[1..].to_owned();
+	} else if operand.starts_with("@") {
+		at_symbol = true;
+		operand = operand.as_str()[1..].to_owned();
+	}
+
+	if operand.contains(",") {
+		operand = operand.split(",").collect::<Vec<&str>>()[0].to_string();
+		x_index = true;
+	}
+
+	let mode = if hash_symbol {
+		AddressingMode::Immediate
+	} else if at_symbol {
+		AddressingMode::Indirect
+	} else {
+		AddressingMode::Simple
+	};
+
+	match instruction_format {
+		1 => {
+			// format 1
+			Ok((to_bytes(opcode_hex, 1), None))
+		}
+		2 => {
+			// format 2
+			let instruction = get_instruction(opcode).unwrap();
+			let (r1, r2) = get_format_2_operands(instruction, &split_operand_list(&register_operand), line_number)?;
+			Ok((to_bytes((opcode_hex << 8) + (r1 << 4) + r2, 2), None))
+		}
+		3 if symbol_table.source_format.sic => {
+			// SIC has no n, i, b, p or e bits, just the x bit and a 15 bit address
+			let symbol_location = symbol_table.get_symbol_location(&operand);
+
+			let mut address = if operand.is_empty() {
+				0
+			} else if symbol_location == -1 {
+				get_numeric_operand(&operand, NumericRange::SicAddress, line_number)?
+			} else {
+				// SIC programs are loaded where they were assembled, so there are no modification records
+				let (_, max, description) = NumericRange::SicAddress.bounds();
+				if symbol_location > max {
+					return Err(Diagnostic::error(line_number, format!("{} is out of range for a {}!", operand, description)));
+				}
+				symbol_location
+			};
+
+			if x_index {
+				address += 32768;
+			}
+
+			let addressing = Addressing {
+				mode,
+				target: TargetAddressing::Direct,
+				indexed: x_index,
+			};
+			Ok((to_bytes((opcode_hex << 16) + address, 3), Some(addressing)))
+		}
+		3 => {
+			// format 3
+			let mut first_byte = opcode_hex;
+
+			if hash_symbol {
+				// i bit flipped
+				first_byte += 1;
+			} else if at_symbol {
+				// n bit flipped
+				first_byte += 2;
+			} else {
+				// n & i bits flipped
+				first_byte += 3;
+			}
+
+			let symbol_location = symbol_table.get_symbol_location(&operand);
+			let mut target = TargetAddressing::Direct;
+
+			let mut displacement = if operand.is_empty() {
+				// RSUB with no operand
+				0
+			} else if symbol_location == -1 {
+				// numeric operands are absolute, either an immediate value or a direct address
+				get_numeric_operand(&operand, NumericRange::Displacement, line_number)?
+			} else {
+				match get_format_3_displacement(symbol_table, line_number, symbol_location) {
+					Some((chosen_target, displacement)) => {
+						target = chosen_target;
+						displacement
+					}
+					None if get_instruction(opcode).is_some_and(|instruction| !instruction.formats.contains(&4)) => {
+						return Err(Diagnostic::error(line_number, format!("{} is out of range for format 3, and {} has no format 4!",
+						                                                  operand, opcode)));
+					}
+					None => {
+						return Err(Diagnostic::error(line_number, format!("{} is out of range for format 3! Use format 4 (+{}) or assemble with --relax.",
+						                                                  operand, opcode)));
+					}
+				}
+			};
+
+			if x_index {
+				// use x-indexing
+				displacement += 32768;
+			}
+
+			let addressing = Addressing {
+				mode,
+				target,
+				indexed: x_index,
+			};
+			Ok((to_bytes((first_byte << 16) + displacement, 3), Some(addressing)))
+		}
+		_ => {
+			// format 4
+			let mut first_byte = opcode_hex;
+
+			if hash_symbol {
+				// i bit flipped
+				first_byte += 1;
+			} else if at_symbol {
+				// n bit flipped
+				first_byte += 2;
+			} else {
+				// n & i bits flipped
+				first_byte += 3;
+			}
+
+			let symbol_location = symbol_table.get_symbol_location(&operand);
+
+			let mut displacement = if operand.is_empty() {
+				0
+			} else if symbol_location == -1 {
+				// numeric operands are absolute, so they need no modification record
+				get_numeric_operand(&operand, NumericRange::Address, line_number)?
+			} else {
+				// since using direct addressing we must add a modification record
+				modifications.push(Modification {
+					line_number,
+					address: current_memory_location + 1,
+					half_bytes: 5,
+					sign: '+',
+					symbol: symbol_table.program_name.clone(),
+				});
+
+				symbol_location
+			};
+
+			if x_index {
+				// use x-indexing
+				displacement += 8388608;
+			}
+
+			// flip e bit
+			displacement += 1048576;
+
+			let addressing = Addressing {
+				mode,
+				target: TargetAddressing::Direct,
+				indexed: x_index,
+			};
+			Ok((to_bytes((first_byte << 24) + displacement, 4), Some(addressing)))
+		}
+	}
+}
+
+fn get_directive_code(symbol_table: &SymbolTable, line_number: usize, directive: &str,
+                      operand: Option<&String>, modifications: &mut Vec<Modification>) -> AsmResult<Vec<u8>> {
+	match directive {
+		"BYTE" => decode_byte_constant(operand.unwrap(), line_number),
+		"WORD" => {
+			let operand = operand.unwrap();
+			let mut current_memory_location = symbol_table.memory_locations[line_number - 1];
+			let mut code = vec![];
+
+			for value in split_operand_list(operand) {
+				let (word, relocation) = symbol_table.evaluate_expression(&value, line_number)?;
+
+				match relocation {
+					0 => {
+						let (min, max, description) = NumericRange::Word.bounds();
+						if word < min || word > max {
+							return Err(Diagnostic::error(line_number, format!("{} is out of range for a {} ({} to {})!", value, description, min, max)));
+						}
+					}
+					1 | -1 => {
+						// relocatable words have all 6 half-bytes adjusted by the loader
+						modifications.push(Modification {
+							line_number,
+							address: current_memory_location,
+							half_bytes: 6,
+							sign: if relocation == 1 { '+' } else { '-' },
+							symbol: symbol_table.program_name.clone(),
+						});
+					}
+					_ => {
+						return Err(Diagnostic::error(line_number, format!("{} is neither an absolute nor a relocatable expression!", value)));
+					}
+				}
+
+				code.extend(to_bytes(word, 3));
+				current_memory_location += 3;
+			}
+			Ok(code)
+		}
+		"END" => {
+			if operand.is_some() && symbol_table.get_symbol_location(operand.unwrap()) == -1 {
+				return Err(Diagnostic::error(line_number, "End directive has invalid symbol!"));
+			}
+			Ok(vec![])
+		}
+		"BASE" => {
+			if operand.is_none() {
+				return Err(Diagnostic::error(line_number, "Base directive has no symbol!"));
+			}
+
+			let symbol_location = symbol_table.get_symbol_location(operand.unwrap());
+			if symbol_location == -1 {
+				return Err(Diagnostic::error(line_number, "Base directive has invalid symbol!"));
+			}
+
+			Ok(vec![])
+		}
+		&_ => {
+			Ok(vec![])
+		}
+	}
+}
+
+fn get_numeric_operand(operand: &str, range: NumericRange, line_number: usize) -> AsmResult<i32> {
+	if parse_numeric_literal(operand, 10).is_none() {
+		return Err(Diagnostic::error(line_number, format!("Undefined symbol {}!", operand)));
+	}
+	parse_number_or_error(Some(operand), 10, range, line_number)
+}
+
+/// Picks pc-relative, base-relative or direct addressing for a format 3 operand, returning the target and
+/// the displacement field with its b/p bits set. Returns None if no 12 bit displacement can reach the symbol.
+/// Direct addressing is only used by absolute programs.
+fn get_format_3_displacement(symbol_table: &SymbolTable, line_number: usize, symbol_location: i32) -> Option<(TargetAddressing, i32)> {
+	let current_memory_location = symbol_table.memory_locations[line_number - 1];
+
+	let program_counter = current_memory_location + 3;
+	let program_counter_displacement = symbol_location - program_counter;
+	let base_location = match &symbol_table.lines[line_number - 1].base {
+		Some(base) => symbol_table.get_symbol_location(base),
+		None => -1,
+	};
+	let base_displacement = if base_location == -1 { 4096 } else { symbol_location - base_location };
+
+	if (-2048..2048).contains(&program_counter_displacement) {
+		// use pc-relative addressing
+		let mut p_bit = 8192;
+		if program_counter_displacement < 0 {
+			p_bit += 4096;
+		}
+		Some((TargetAddressing::PcRelative, program_counter_displacement + p_bit))
+	} else if (0..4096).contains(&base_displacement) {
+		// use base-relative addressing
+		let b_bit = 16384;
+		Some((TargetAddressing::BaseRelative, base_displacement + b_bit))
+	} else if !symbol_table.is_relocatable() && (0..4096).contains(&symbol_location) {
+		// direct addressing only works for the first 4096 bytes of memory, and a relocatable program
+		// can't use it at all since a 12 bit address field can't be relocated past 4096
+		Some((TargetAddressing::Direct, symbol_location))
+	} else {
+		None
+	}
+}
+
+/// Runs pass 1 until every format 3 instruction can reach its operand, promoting the ones that
+/// can't to format 4. Promoting an instruction moves everything after it, so this repeats until
+/// no new instruction needs promoting.
+pub fn relax_symbol_table(lines: &[String], source_format: SourceFormat) -> AsmResult<SymbolTable> {
+	let mut relaxed_lines: Vec<usize> = vec![];
+
+	loop {
+		let mut symbol_table = SymbolTable::new();
+		symbol_table.relaxed_lines = relaxed_lines.clone();
+		symbol_table.source_format = source_format;
+		symbol_table.parse_symbol_table(lines)?;
+
+		let unreachable_lines = find_unreachable_lines(&symbol_table);
+		if unreachable_lines.is_empty() {
+			return Ok(symbol_table);
+		}
+		relaxed_lines.extend(unreachable_lines);
+	}
+}
+
+fn find_unreachable_lines(symbol_table: &SymbolTable) -> Vec<usize> {
+	let mut unreachable_lines = vec![];
+
+	// SIC addresses every byte directly and has no format 4 to promote to
+	if symbol_table.source_format.sic {
+		return unreachable_lines;
+	}
+
+	for (index, line) in symbol_table.lines.iter().enumerate() {
+		let (operation, operand) = match (&line.operation, &line.operand) {
+			(Some(operation), Some(operand)) => (operation, operand),
+			_ => continue,
+		};
+		// only instructions with a format 4 can be promoted, the others get the out of range error
+		let promotable = get_instruction(operation).is_some_and(|instruction| instruction.formats.contains(&4));
+		if !promotable || get_instruction_format(operation) != 3 {
+			continue;
+		}
+
+		let operand = operand.trim_start_matches(['#', '@']);
+		let operand = operand.split(",").next().unwrap().to_string();
+
+		let symbol_location = symbol_table.get_symbol_location(&operand);
+		if symbol_location != -1 && get_format_3_displacement(symbol_table, index + 1, symbol_location).is_none() {
+			unreachable_lines.push(index + 1);
+		}
+	}
+
+	unreachable_lines
+}
+
+/// Returns the value an immediate operand (#SYMBOL or #number) loads, if it is immediate.
+fn get_immediate_value(symbol_table: &SymbolTable, operand: Option<&String>) -> Option<i32> {
+	let operand = operand?.strip_prefix("#")?;
+
+	let symbol_location = symbol_table.get_symbol_location(operand);
+	if symbol_location != -1 {
+		return Some(symbol_location);
+	}
+	parse_numeric_literal(operand, 10)
+}
+
+fn to_bytes(value: i32, num_bytes: usize) -> Vec<u8> {
+	(0..num_bytes).rev().map(|index| (value >> (index * 8)) as u8).collect()
+}
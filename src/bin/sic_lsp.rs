@@ -0,0 +1,426 @@
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::io::{BufRead, Write};
+use std::process::exit;
+
+use serde_json::{json, Value};
+
+use sic_assembler_rust::assembler::*;
+use sic_assembler_rust::instructions::*;
+use sic_assembler_rust::symbols::*;
+use sic_assembler_rust::util::*;
+
+// LSP constants used below
+const TEXT_DOCUMENT_SYNC_FULL: i32 = 1;
+const SEVERITY_ERROR: i32 = 1;
+const SEVERITY_WARNING: i32 = 2;
+const COMPLETION_KEYWORD: i32 = 14;
+const COMPLETION_FUNCTION: i32 = 3;
+const COMPLETION_VARIABLE: i32 = 6;
+const SYMBOL_FUNCTION: i32 = 12;
+const SYMBOL_VARIABLE: i32 = 13;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+/// An open document with the result of assembling it. Pass 1 failing leaves no symbol table
+/// and pass 2 failing leaves no object code, the error is in the diagnostics either way.
+struct Document {
+	lines: Vec<String>,
+	symbol_table: Option<SymbolTable>,
+	object_code: Option<ObjectCode>,
+	diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+	Label,
+	Operation,
+	Operand,
+}
+
+/// A label, operation or a name inside an operand, with its character columns on the line.
+/// Labels and names hold the qualified name pass 1 stores them under.
+struct Token {
+	field: Field,
+	text: String,
+	start: usize,
+	end: usize,
+}
+
+fn main() {
+	// same instruction set extension and --long-names options as the assembler
+	let args: Vec<String> = env::args().collect();
+	if let Some(position) = args.iter().position(|arg| arg == "--instructions") {
+		let filename = args.get(position + 1).map(|filename| filename.as_str()).unwrap_or_default();
+		let lines = read_source(filename).unwrap_or_else(|error| {
+			eprintln!("Could not read {}: {}", filename, error);
+			exit(1);
+		});
+		if let Err(error) = load_instruction_set(&lines) {
+			eprintln!("{}: {}", filename, error);
+			exit(1);
+		}
+	}
+	let long_names = args.iter().any(|arg| arg == "--long-names");
+
+	let mut stdin = io::stdin().lock();
+	let mut stdout = io::stdout().lock();
+	let mut documents: HashMap<String, Document> = HashMap::new();
+	let mut shutdown = false;
+
+	while let Some(message) = read_message(&mut stdin) {
+		let method = message["method"].as_str().unwrap_or_default();
+		let params = &message["params"];
+
+		let result = match method {
+			"initialize" => Some(json!({
+				"capabilities": {
+					"textDocumentSync": TEXT_DOCUMENT_SYNC_FULL,
+					"hoverProvider": true,
+					"definitionProvider": true,
+					"referencesProvider": true,
+					"completionProvider": {},
+					"documentSymbolProvider": true,
+				},
+				"serverInfo": { "name": "sic_lsp" },
+			})),
+			"shutdown" => {
+				shutdown = true;
+				Some(Value::Null)
+			}
+			"exit" => exit(if shutdown { 0 } else { 1 }),
+			"textDocument/didOpen" | "textDocument/didChange" => {
+				let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+				// full sync, so the last change holds the whole text
+				let text = match method {
+					"textDocument/didOpen" => params["textDocument"]["text"].as_str(),
+					_ => params["contentChanges"].as_array().and_then(|changes| changes.last()).and_then(|change| change["text"].as_str()),
+				};
+
+				let document = analyze(text.unwrap_or_default(), long_names);
+				write_message(&mut stdout, &json!({
+					"jsonrpc": "2.0",
+					"method": "textDocument/publishDiagnostics",
+					"params": { "uri": uri, "diagnostics": diagnostics_to_json(&document) },
+				}));
+				documents.insert(uri, document);
+				None
+			}
+			"textDocument/didClose" => {
+				let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+				documents.remove(uri);
+				write_message(&mut stdout, &json!({
+					"jsonrpc": "2.0",
+					"method": "textDocument/publishDiagnostics",
+					"params": { "uri": uri, "diagnostics": [] },
+				}));
+				None
+			}
+			"textDocument/hover" | "textDocument/definition" | "textDocument/references"
+			| "textDocument/completion" | "textDocument/documentSymbol" => {
+				let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+				let line = params["position"]["line"].as_u64().unwrap_or_default() as usize;
+				let character = params["position"]["character"].as_u64().unwrap_or_default() as usize;
+
+				Some(match documents.get(uri) {
+					Some(document) => match method {
+						"textDocument/hover" => hover(document, line, character),
+						"textDocument/definition" => definition(document, uri, line, character),
+						"textDocument/references" => {
+							let include_declaration = params["context"]["includeDeclaration"].as_bool().unwrap_or(true);
+							references(document, uri, line, character, include_declaration)
+						}
+						"textDocument/completion" => completion(document),
+						_ => document_symbols(document, uri),
+					},
+					None => Value::Null,
+				})
+			}
+			_ if message.get("id").is_some() => {
+				write_message(&mut stdout, &json!({
+					"jsonrpc": "2.0",
+					"id": message["id"],
+					"error": { "code": METHOD_NOT_FOUND, "message": format!("Unknown method {}", method) },
+				}));
+				None
+			}
+			// other notifications need no answer
+			_ => None,
+		};
+
+		if let Some(result) = result {
+			write_message(&mut stdout, &json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }));
+		}
+	}
+}
+
+/// Reads one message with its Content-Length header, None at the end of the input.
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+	let mut content_length: Option<usize> = None;
+
+	loop {
+		let mut header = String::new();
+		if input.read_line(&mut header).ok()? == 0 {
+			return None;
+		}
+		let header = header.trim();
+		if header.is_empty() {
+			break;
+		}
+		if let Some(length) = header.strip_prefix("Content-Length:") {
+			content_length = length.trim().parse().ok();
+		}
+	}
+
+	let mut body = vec![0; content_length?];
+	input.read_exact(&mut body).ok()?;
+	serde_json::from_slice(&body).ok()
+}
+
+fn write_message(output: &mut impl Write, message: &Value) {
+	let body = message.to_string();
+	// the client going away ends the server on the next read anyway
+	let _ = write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body);
+	let _ = output.flush();
+}
+
+/// Runs both passes over a document the same way the assembler does.
+fn analyze(text: &str, long_names: bool) -> Document {
+	let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+	let mut document = Document { lines, symbol_table: None, object_code: None, diagnostics: vec![] };
+
+	let mut symbol_table = SymbolTable::new();
+	symbol_table.source_format.fixed_columns = detect_fixed_columns(&document.lines);
+	symbol_table.source_format.long_names = long_names;
+	if let Err(error) = symbol_table.parse_symbol_table(&document.lines) {
+		document.diagnostics.push(error);
+		return document;
+	}
+	document.diagnostics.extend(symbol_table.warnings.iter().cloned());
+
+	match assemble(&mut symbol_table) {
+		Ok(object_code) => {
+			document.diagnostics.extend(object_code.warnings.iter().cloned());
+			document.object_code = Some(object_code);
+		}
+		Err(error) => document.diagnostics.push(error),
+	}
+	document.symbol_table = Some(symbol_table);
+
+	document
+}
+
+fn diagnostics_to_json(document: &Document) -> Vec<Value> {
+	document.diagnostics.iter().map(|diagnostic| {
+		// errors about the whole file go on the first line
+		let line = diagnostic.line_number.saturating_sub(1);
+		let length = document.lines.get(line).map(|line| line.chars().count()).unwrap_or(0);
+
+		json!({
+			"range": range(line, 0, length),
+			"severity": match diagnostic.severity {
+				Severity::Error => SEVERITY_ERROR,
+				Severity::Warning => SEVERITY_WARNING,
+			},
+			"source": "sic",
+			"message": diagnostic.message,
+		})
+	}).collect()
+}
+
+fn hover(document: &Document, line: usize, character: usize) -> Value {
+	let (symbol_table, token) = match (&document.symbol_table, token_at(document, line, character)) {
+		(Some(symbol_table), Some(token)) => (symbol_table, token),
+		_ => return Value::Null,
+	};
+
+	let contents = if token.field == Field::Operation {
+		let address = symbol_table.memory_locations[line];
+		if is_directive(&token.text) {
+			format!("**{}** directive\n\nAddress {:0>6X}", token.text, address)
+		} else {
+			let instruction = get_instruction(&token.text).unwrap();
+			let operation = symbol_table.lines[line].operation.as_deref().unwrap_or_default();
+			let mut contents = format!("**{}** opcode {:0>2X}, format {}", instruction.mnemonic, instruction.opcode,
+			                           get_instruction_format(operation));
+			if instruction.xe_only {
+				contents.push_str(", SIC/XE only");
+			}
+			if instruction.privileged {
+				contents.push_str(", privileged");
+			}
+			contents.push_str(&format!("\n\nAddress {:0>6X}", address));
+			let assembled_line = document.object_code.as_ref().map(|object_code| &object_code.lines[line]);
+			if let Some(addressing) = assembled_line.and_then(|assembled_line| assembled_line.addressing) {
+				contents.push_str(&format!(", {} addressing", addressing));
+			}
+			if let Some(assembled_line) = assembled_line {
+				contents.push_str(&format!("\n\nObject code {}", hex::encode_upper(&assembled_line.code)));
+			}
+			contents
+		}
+	} else {
+		let symbol = match symbol_table.get_symbol(&token.text) {
+			Some(symbol) => symbol,
+			None => return Value::Null,
+		};
+		format!("**{}** {:0>6X} ({})\n\nDefined on line {}, length {}", symbol.name, symbol.memory_location,
+		        if symbol.relative { "relative" } else { "absolute" }, symbol.line_number, symbol.length)
+	};
+
+	json!({
+		"contents": { "kind": "markdown", "value": contents },
+		"range": range(line, token.start, token.end),
+	})
+}
+
+fn describe_formats(instruction: &Instruction) -> String {
+	instruction.formats.iter().map(|format| format.to_string()).collect::<Vec<String>>().join("/")
+}
+
+fn definition(document: &Document, uri: &str, line: usize, character: usize) -> Value {
+	let symbol = match symbol_at(document, line, character) {
+		Some(symbol) => symbol,
+		None => return Value::Null,
+	};
+
+	let defining_line = symbol.line_number - 1;
+	match tokens(document, defining_line).into_iter().find(|token| token.field == Field::Label) {
+		Some(label) => location(uri, defining_line, label.start, label.end),
+		None => Value::Null,
+	}
+}
+
+fn references(document: &Document, uri: &str, line: usize, character: usize, include_declaration: bool) -> Value {
+	let symbol = match symbol_at(document, line, character) {
+		Some(symbol) => symbol,
+		None => return Value::Null,
+	};
+
+	let mut locations = vec![];
+	for line in 0..document.lines.len() {
+		for token in tokens(document, line) {
+			if token.field == Field::Operation || (token.field == Field::Label && !include_declaration) {
+				continue;
+			}
+			if token.text == symbol.name {
+				locations.push(location(uri, line, token.start, token.end));
+			}
+		}
+	}
+	Value::Array(locations)
+}
+
+fn completion(document: &Document) -> Value {
+	let mut items: Vec<Value> = instruction_set().iter().map(|instruction| json!({
+		"label": instruction.mnemonic,
+		"kind": COMPLETION_KEYWORD,
+		"detail": format!("opcode {:0>2X}, format {}", instruction.opcode, describe_formats(instruction)),
+	})).collect();
+
+	items.extend(DIRECTIVES.iter().map(|directive| json!({
+		"label": directive,
+		"kind": COMPLETION_KEYWORD,
+		"detail": "directive",
+	})));
+
+	if let Some(symbol_table) = &document.symbol_table {
+		// local labels are only meaningful inside their scope, so only global labels are offered
+		items.extend(symbol_table.symbols().iter().filter(|symbol| !symbol.name.contains('.')).map(|symbol| json!({
+			"label": symbol.name,
+			"kind": if is_instruction_label(symbol_table, symbol) { COMPLETION_FUNCTION } else { COMPLETION_VARIABLE },
+			"detail": format!("{:0>6X}", symbol.memory_location),
+		})));
+	}
+
+	Value::Array(items)
+}
+
+fn document_symbols(document: &Document, uri: &str) -> Value {
+	let symbol_table = match &document.symbol_table {
+		Some(symbol_table) => symbol_table,
+		None => return Value::Array(vec![]),
+	};
+
+	let symbols = symbol_table.symbols().iter().filter_map(|symbol| {
+		let line = symbol.line_number - 1;
+		let label = tokens(document, line).into_iter().find(|token| token.field == Field::Label)?;
+		Some(json!({
+			"name": symbol.name,
+			"kind": if is_instruction_label(symbol_table, symbol) { SYMBOL_FUNCTION } else { SYMBOL_VARIABLE },
+			"location": location(uri, line, label.start, label.end),
+		}))
+	}).collect();
+
+	Value::Array(symbols)
+}
+
+// labels on instructions are jump targets, everything else is data
+fn is_instruction_label(symbol_table: &SymbolTable, symbol: &Symbol) -> bool {
+	symbol_table.lines[symbol.line_number - 1].operation.as_deref().is_some_and(is_instruction)
+}
+
+fn symbol_at(document: &Document, line: usize, character: usize) -> Option<&Symbol> {
+	let symbol_table = document.symbol_table.as_ref()?;
+	let token = token_at(document, line, character)?;
+	if token.field == Field::Operation {
+		return None;
+	}
+	symbol_table.get_symbol(&token.text)
+}
+
+fn token_at(document: &Document, line: usize, character: usize) -> Option<Token> {
+	tokens(document, line).into_iter().find(|token| token.start <= character && character <= token.end)
+}
+
+/// The label, operation and operand names on a line. Comments produce no tokens.
+fn tokens(document: &Document, line: usize) -> Vec<Token> {
+	let (symbol_table, text) = match (&document.symbol_table, document.lines.get(line)) {
+		(Some(symbol_table), Some(text)) => (symbol_table, text),
+		_ => return vec![],
+	};
+	let source_line = &symbol_table.lines[line];
+
+	let mut fields = source_fields(text, &symbol_table.source_format).into_iter();
+	let mut tokens = vec![];
+
+	if let Some(label) = &source_line.label {
+		if let Some((start, text)) = fields.next() {
+			tokens.push(Token { field: Field::Label, text: label.clone(), start, end: start + text.chars().count() });
+		}
+	}
+
+	let operation = match &source_line.operation {
+		Some(operation) => operation.trim_start_matches("+"),
+		None => return tokens,
+	};
+	if let Some((start, text)) = fields.next() {
+		// the '+' of format 4 isn't part of the mnemonic
+		let start = start + text.len() - text.trim_start_matches("+").len();
+		tokens.push(Token { field: Field::Operation, text: operation.to_string(), start, end: start + operation.chars().count() });
+	}
+
+	// pass 1 already qualified the local labels in the operand, in the same order as they're written
+	if let (Some(resolved), Some((start, operand))) = (&source_line.operand, fields.next()) {
+		for ((offset, name), (_, qualified_name)) in operand_names(&operand).into_iter().zip(operand_names(resolved)) {
+			if parse_numeric_literal(&name, 10).is_some() {
+				continue;
+			}
+			let start = start + offset;
+			tokens.push(Token { field: Field::Operand, text: qualified_name, start, end: start + name.chars().count() });
+		}
+	}
+
+	tokens
+}
+
+fn range(line: usize, start: usize, end: usize) -> Value {
+	json!({
+		"start": { "line": line, "character": start },
+		"end": { "line": line, "character": end },
+	})
+}
+
+fn location(uri: &str, line: usize, start: usize, end: usize) -> Value {
+	json!({ "uri": uri, "range": range(line, start, end) })
+}
@@ -0,0 +1,125 @@
+use crate::instructions::*;
+use crate::symbols::*;
+use crate::util::*;
+
+// label, opcode and operand fields start on multiples of this column when indenting with spaces
+const FIELD_WIDTH: usize = 8;
+
+/// Rewrites source lines with aligned fields, uppercase mnemonics and symbols and normalized
+/// numeric literals. Comments and blank lines are kept as they are.
+pub fn format_source(lines: &[String], tabs: bool) -> Vec<String> {
+	lines.iter().map(|line| format_line(line, tabs)).collect()
+}
+
+fn format_line(line: &str, tabs: bool) -> String {
+	let fields = sic_line_to_vector(line.to_string(), false);
+	if fields.is_empty() || line.starts_with("#") || is_dot_comment(line) {
+		return line.trim_end().to_string();
+	}
+
+	// same field rules as pass 1: anything in column 1 is a label
+	let mut fields = fields.into_iter();
+	let label = if line.starts_with(char::is_whitespace) { String::new() } else { fields.next().unwrap() };
+	let operation = fields.next().map(|operation| operation.to_uppercase()).unwrap_or_default();
+	let operand = if !operation.is_empty() && takes_operand(&operation) { fields.next() } else { None };
+
+	let consumed = [!label.is_empty(), !operation.is_empty(), operand.is_some()].iter().filter(|field| **field).count();
+	let comment = skip_fields(line, consumed);
+
+	let columns = [label.to_uppercase(), operation, operand.map(|operand| normalize_operand(&operand)).unwrap_or_default(),
+	               comment.to_string()];
+	let last = columns.iter().rposition(|column| !column.is_empty()).unwrap_or(0);
+
+	let mut formatted = String::new();
+	for (index, column) in columns[..=last].iter().enumerate() {
+		formatted.push_str(column);
+		if index == last {
+			break;
+		}
+
+		if tabs {
+			formatted.push('\t');
+		} else {
+			let width = (formatted.len() / FIELD_WIDTH + 1) * FIELD_WIDTH;
+			formatted.push_str(&" ".repeat(width - formatted.len()));
+		}
+	}
+	formatted
+}
+
+// the rest of a line after `count` whitespace separated fields, which is its comment
+fn skip_fields(line: &str, count: usize) -> &str {
+	let mut rest = line;
+
+	for _ in 0..count {
+		rest = rest.trim_start();
+		let mut in_string = false;
+		let end = rest.char_indices()
+			.find(|(_, c)| {
+				if *c == '\'' {
+					in_string = !in_string;
+				}
+				c.is_whitespace() && !in_string
+			})
+			.map(|(index, _)| index)
+			.unwrap_or(rest.len());
+		rest = &rest[end..];
+	}
+
+	rest.trim()
+}
+
+/// Uppercases symbols, registers and constant prefixes and writes numbers in one way:
+/// 0x1f becomes X'1F', hex digits are uppercase and decimal numbers lose leading zeros.
+/// Characters inside C'..' constants are left alone.
+fn normalize_operand(operand: &str) -> String {
+	// the same list splitting as BYTE and WORD, so a '' quote doesn't end a C'..' constant
+	split_operand_list(operand).iter().map(|value| normalize_value(value)).collect::<Vec<String>>().join(",")
+}
+
+fn normalize_value(value: &str) -> String {
+	let (start, end) = match (value.find('\''), value.rfind('\'')) {
+		(Some(start), Some(end)) if start < end => (start, end + 1),
+		_ => return normalize_names(value),
+	};
+
+	let prefix = value[..start].rsplit(|c: char| !is_name_character(c)).next().unwrap_or_default();
+	let constant = &value[start..end];
+	let constant = if prefix.eq_ignore_ascii_case("C") { constant.to_string() } else { constant.to_uppercase() };
+
+	format!("{}{}{}", normalize_names(&value[..start]), constant, normalize_names(&value[end..]))
+}
+
+// normalizes every name or number in text without quoted constants
+fn normalize_names(text: &str) -> String {
+	let mut normalized = String::new();
+	let mut name = String::new();
+
+	for c in text.chars() {
+		if is_name_character(c) {
+			name.push(c);
+		} else {
+			normalized.push_str(&normalize_name(&name));
+			normalized.push(c);
+			name.clear();
+		}
+	}
+	normalized.push_str(&normalize_name(&name));
+
+	normalized
+}
+
+fn is_name_character(c: char) -> bool {
+	c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+fn normalize_name(name: &str) -> String {
+	if let Some(digits) = name.strip_prefix("0x").or_else(|| name.strip_prefix("0X")) {
+		return format!("X'{}'", digits.to_uppercase());
+	}
+	if !name.is_empty() && name.chars().all(|c| c.is_ascii_digit()) {
+		let trimmed = name.trim_start_matches('0');
+		return if trimmed.is_empty() { "0".to_string() } else { trimmed.to_string() };
+	}
+	name.to_uppercase()
+}
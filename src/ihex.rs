@@ -0,0 +1,44 @@
+use std::io;
+
+use crate::image::*;
+use crate::util::*;
+
+pub fn write_hex_file(output_file: &str, image: &MemoryImage) -> io::Result<()> {
+	let mut records: Vec<String> = vec![];
+	let mut upper_address = 0;
+
+	// data records only carry 16 address bits, so split on every 64K boundary
+	for (address, bytes) in image.chunks(16) {
+		let split = (0x10000 - (address & 0xFFFF)) as usize;
+		let pieces = if split < bytes.len() {
+			vec![(address, &bytes[..split]), (address + split as i32, &bytes[split..])]
+		} else {
+			vec![(address, bytes)]
+		};
+
+		for (address, bytes) in pieces {
+			if address >> 16 != upper_address {
+				upper_address = address >> 16;
+				records.push(hex_record(0, 4, &[(upper_address >> 8) as u8, upper_address as u8]));
+			}
+			records.push(hex_record(address & 0xFFFF, 0, bytes));
+		}
+	}
+
+	let entry_point = image.entry_point;
+	records.push(hex_record(0, 5, &[(entry_point >> 24) as u8, (entry_point >> 16) as u8,
+		(entry_point >> 8) as u8, entry_point as u8]));
+	records.push(hex_record(0, 1, &[]));
+
+	write_lines(output_file, records)
+}
+
+fn hex_record(address: i32, record_type: u8, data: &[u8]) -> String {
+	let mut bytes = vec![data.len() as u8, (address >> 8) as u8, address as u8, record_type];
+	bytes.extend_from_slice(data);
+
+	let sum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+	bytes.push(sum.wrapping_neg());
+
+	format!(":{}", hex::encode_upper(bytes))
+}
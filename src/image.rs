@@ -0,0 +1,87 @@
+use std::io;
+
+use crate::assembler::*;
+use crate::symbols::*;
+use crate::util::*;
+
+pub struct MemoryImage {
+	pub load_address: i32,
+	pub entry_point: i32,
+	pub bytes: Vec<u8>,
+	pub defined: Vec<bool>,
+}
+
+impl MemoryImage {
+	pub fn new(symbol_table: &SymbolTable, object_code: &ObjectCode, load_address: i32, fill: u8) -> MemoryImage {
+		let length = symbol_table.total_memory_usage.max(0) as usize;
+		let mut image = MemoryImage {
+			load_address,
+			entry_point: load_address + symbol_table.entry_point() - symbol_table.starting_memory_location,
+			bytes: vec![fill; length],
+			defined: vec![false; length],
+		};
+
+		for line in &object_code.lines {
+			let offset = (line.address - symbol_table.starting_memory_location) as usize;
+			for (index, byte) in line.code.iter().enumerate() {
+				image.bytes[offset + index] = *byte;
+				image.defined[offset + index] = true;
+			}
+		}
+
+		// the image has no relocation records, so every modification is applied up front
+		let relocation = load_address - symbol_table.starting_memory_location;
+		for modification in &object_code.modifications {
+			let offset = (modification.address - symbol_table.starting_memory_location) as usize;
+			let relocation = if modification.sign == '-' { -relocation } else { relocation };
+			image.relocate(offset, modification.half_bytes, relocation);
+		}
+
+		image
+	}
+
+	/// Adds `relocation` to the field made of the rightmost `half_bytes` nibbles of the bytes at `offset`.
+	fn relocate(&mut self, offset: usize, half_bytes: i32, relocation: i32) {
+		let num_bytes = ((half_bytes + 1) / 2) as usize;
+
+		let mut value: i32 = 0;
+		for index in 0..num_bytes {
+			value = (value << 8) + self.bytes[offset + index] as i32;
+		}
+
+		let mask = (1 << (half_bytes * 4)) - 1;
+		let field = ((value & mask) + relocation) & mask;
+		value = (value & !mask) | field;
+
+		for index in (0..num_bytes).rev() {
+			self.bytes[offset + index] = value as u8;
+			value >>= 8;
+		}
+	}
+
+	/// Returns each run of assembled bytes as (address, bytes), never longer than `max_length`.
+	/// Gaps left by RESB/RESW are skipped.
+	pub fn chunks(&self, max_length: usize) -> Vec<(i32, &[u8])> {
+		let mut chunks = vec![];
+		let mut index = 0;
+
+		while index < self.bytes.len() {
+			if !self.defined[index] {
+				index += 1;
+				continue;
+			}
+
+			let start = index;
+			while index < self.bytes.len() && self.defined[index] && index - start < max_length {
+				index += 1;
+			}
+			chunks.push((self.load_address + start as i32, &self.bytes[start..index]));
+		}
+
+		chunks
+	}
+}
+
+pub fn write_binary_file(output_file: &str, image: &MemoryImage) -> io::Result<()> {
+	write_bytes(output_file, &image.bytes)
+}
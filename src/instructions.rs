@@ -1,105 +1,355 @@
-const INSTRUCTIONS: &'static [(&str, i32)] = &[
-	("ADD", 0x18),
-	("ADDF", 0x58),
-	("ADDR", 0x90),
-	("AND", 0x40),
-	("CLEAR", 0xB4),
-	("COMP", 0x28),
-	("COMPF", 0x88),
-	("COMPR", 0xA0),
-	("DIV", 0x24),
-	("DIVF", 0x64),
-	("DIVR", 0x9C),
-	("FIX", 0xC4),
-	("FLOAT", 0xC0),
-	("HIO", 0xF4),
-	("J", 0x3C),
-	("JEQ", 0x30),
-	("JGT", 0x34),
-	("JLT", 0x38),
-	("JSUB", 0x48),
-	("LDA", 0x00),
-	("LDB", 0x68),
-	("LDCH", 0x50),
-	("LDF", 0x70),
-	("LDL", 0x08),
-	("LDS", 0x6C),
-	("LDT", 0x74),
-	("LDX", 0x04),
-	("LPS", 0xD0),
-	("MUL", 0x20),
-	("MULF", 0x60),
-	("MULR", 0x98),
-	("NORM", 0xC8),
-	("OR", 0x44),
-	("RD", 0xD8),
-	("RMO", 0xAC),
-	("RSUB", 0x4C),
-	("SHIFTL", 0xA4),
-	("SHIFTR", 0xA8),
-	("SIO", 0xF0),
-	("SSK", 0xEC),
-	("STA", 0x0C),
-	("STB", 0x78),
-	("STCH", 0x54),
-	("STF", 0x80),
-	("STI", 0xD4),
-	("STL", 0x14),
-	("STS", 0x7C),
-	("STSW", 0xE8),
-	("STT", 0x84),
-	("STX", 0x10),
-	("SUB", 0x1C),
-	("SUBF", 0x5C),
-	("SUBR", 0x94),
-	("SVC", 0xB0),
-	("TD", 0xE0),
-	("TIO", 0xF8),
-	("TIX", 0x2C),
-	("TIXR", 0xB8),
-	("WD", 0xDC)
+use std::sync::OnceLock;
+
+use crate::util::*;
+
+/// What the operand field of an instruction holds.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OperandSignature {
+	/// no operand, anything after the mnemonic is a comment
+	None,
+	/// one register (r1)
+	Register,
+	/// two registers (r1,r2)
+	RegisterPair,
+	/// a register and a count (r1,n)
+	RegisterCount,
+	/// a number (n)
+	Number,
+	/// a memory address or immediate value, optionally indexed (m or m,X)
+	Memory,
+}
+
+/// One entry of the instruction set.
+#[derive(Clone, Copy, Debug)]
+pub struct Instruction {
+	pub mnemonic: &'static str,
+	pub opcode: i32,
+	/// Formats the instruction can be assembled in, format 3 instructions can also be format 4
+	pub formats: &'static [i32],
+	pub signature: OperandSignature,
+	/// Only exists on SIC/XE machines
+	pub xe_only: bool,
+	/// Only allowed in supervisor mode
+	pub privileged: bool,
+	/// Writes to its memory operand
+	pub stores: bool,
+}
+
+const FORMAT_1: &[i32] = &[1];
+const FORMAT_2: &[i32] = &[2];
+const FORMAT_3: &[i32] = &[3];
+const FORMAT_3_4: &[i32] = &[3, 4];
+
+/// The built-in table with an extension file applied, set once before assembling.
+static INSTRUCTION_SET: OnceLock<Vec<Instruction>> = OnceLock::new();
+
+pub const INSTRUCTIONS: &[Instruction] = &[
+	Instruction { mnemonic: "ADD",    opcode: 0x18, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "ADDF",   opcode: 0x58, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "ADDR",   opcode: 0x90, formats: FORMAT_2,   signature: OperandSignature::RegisterPair,  xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "AND",    opcode: 0x40, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "CLEAR",  opcode: 0xB4, formats: FORMAT_2,   signature: OperandSignature::Register,      xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "COMP",   opcode: 0x28, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "COMPF",  opcode: 0x88, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "COMPR",  opcode: 0xA0, formats: FORMAT_2,   signature: OperandSignature::RegisterPair,  xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "DIV",    opcode: 0x24, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "DIVF",   opcode: 0x64, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "DIVR",   opcode: 0x9C, formats: FORMAT_2,   signature: OperandSignature::RegisterPair,  xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "FIX",    opcode: 0xC4, formats: FORMAT_1,   signature: OperandSignature::None,          xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "FLOAT",  opcode: 0xC0, formats: FORMAT_1,   signature: OperandSignature::None,          xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "HIO",    opcode: 0xF4, formats: FORMAT_1,   signature: OperandSignature::None,          xe_only: true,  privileged: true,  stores: false },
+	Instruction { mnemonic: "J",      opcode: 0x3C, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "JEQ",    opcode: 0x30, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "JGT",    opcode: 0x34, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "JLT",    opcode: 0x38, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "JSUB",   opcode: 0x48, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "LDA",    opcode: 0x00, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "LDB",    opcode: 0x68, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "LDCH",   opcode: 0x50, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "LDF",    opcode: 0x70, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "LDL",    opcode: 0x08, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "LDS",    opcode: 0x6C, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "LDT",    opcode: 0x74, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "LDX",    opcode: 0x04, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "LPS",    opcode: 0xD0, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: true,  stores: false },
+	Instruction { mnemonic: "MUL",    opcode: 0x20, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "MULF",   opcode: 0x60, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "MULR",   opcode: 0x98, formats: FORMAT_2,   signature: OperandSignature::RegisterPair,  xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "NORM",   opcode: 0xC8, formats: FORMAT_1,   signature: OperandSignature::None,          xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "OR",     opcode: 0x44, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "RD",     opcode: 0xD8, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: true,  stores: false },
+	Instruction { mnemonic: "RMO",    opcode: 0xAC, formats: FORMAT_2,   signature: OperandSignature::RegisterPair,  xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "RSUB",   opcode: 0x4C, formats: FORMAT_3_4, signature: OperandSignature::None,          xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "SHIFTL", opcode: 0xA4, formats: FORMAT_2,   signature: OperandSignature::RegisterCount, xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "SHIFTR", opcode: 0xA8, formats: FORMAT_2,   signature: OperandSignature::RegisterCount, xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "SIO",    opcode: 0xF0, formats: FORMAT_1,   signature: OperandSignature::None,          xe_only: true,  privileged: true,  stores: false },
+	Instruction { mnemonic: "SSK",    opcode: 0xEC, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: true,  stores: false },
+	Instruction { mnemonic: "STA",    opcode: 0x0C, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: true },
+	Instruction { mnemonic: "STB",    opcode: 0x78, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: true },
+	Instruction { mnemonic: "STCH",   opcode: 0x54, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: true },
+	Instruction { mnemonic: "STF",    opcode: 0x80, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: true },
+	Instruction { mnemonic: "STI",    opcode: 0xD4, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: true,  stores: false },
+	Instruction { mnemonic: "STL",    opcode: 0x14, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: true },
+	Instruction { mnemonic: "STS",    opcode: 0x7C, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: true },
+	Instruction { mnemonic: "STSW",   opcode: 0xE8, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: true,  stores: true },
+	Instruction { mnemonic: "STT",    opcode: 0x84, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: true },
+	Instruction { mnemonic: "STX",    opcode: 0x10, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: true },
+	Instruction { mnemonic: "SUB",    opcode: 0x1C, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "SUBF",   opcode: 0x5C, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "SUBR",   opcode: 0x94, formats: FORMAT_2,   signature: OperandSignature::RegisterPair,  xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "SVC",    opcode: 0xB0, formats: FORMAT_2,   signature: OperandSignature::Number,        xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "TD",     opcode: 0xE0, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: true,  stores: false },
+	Instruction { mnemonic: "TIO",    opcode: 0xF8, formats: FORMAT_1,   signature: OperandSignature::None,          xe_only: true,  privileged: true,  stores: false },
+	Instruction { mnemonic: "TIX",    opcode: 0x2C, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: false, stores: false },
+	Instruction { mnemonic: "TIXR",   opcode: 0xB8, formats: FORMAT_2,   signature: OperandSignature::Register,      xe_only: true,  privileged: false, stores: false },
+	Instruction { mnemonic: "WD",     opcode: 0xDC, formats: FORMAT_3_4, signature: OperandSignature::Memory,        xe_only: false, privileged: true,  stores: false },
 ];
 
-const FORMAT_1: &'static [&str] = &["FIX", "FLOAT", "HIO", "NORM", "SIO", "TIO"];
-const FORMAT_2: &'static [&str] = &["ADDR", "CLEAR", "COMPR", "DIVR", "MULR", "RMO", "SHIFTL",
-	"SHIFTR", "SUBR", "SVC", "TIXR"];
+/// Register names and the numbers format 2 instructions encode them as.
+pub const REGISTERS: &[(&str, i32)] = &[("A", 0), ("X", 1), ("L", 2), ("B", 3), ("S", 4), ("T", 5), ("F", 6),
+	("PC", 8), ("SW", 9)];
 
-const DIRECTIVES: &'static [&str] = &["START", "END", "BYTE", "WORD", "RESB", "RESW", "RESR", "EXPORTS", "BASE"];
+pub const DIRECTIVES: &[&str] = &["START", "END", "BYTE", "WORD", "RESB", "RESW", "RESR", "EXPORTS", "BASE", "NOBASE"];
 
-pub fn is_instruction(str: &str) -> bool {
-	let str = str.trim_start_matches("+");
-	for (instruction, _hex) in INSTRUCTIONS {
-		if *instruction == str {
-			return true;
+/// The instructions every pass uses: the built-in table, or the table with an extension file applied.
+pub fn instruction_set() -> &'static [Instruction] {
+	INSTRUCTION_SET.get().map(|instructions| instructions.as_slice()).unwrap_or(INSTRUCTIONS)
+}
+
+/// Applies an instruction set extension for the rest of the run. Each line is either
+/// `MNEMONIC OPCODE FORMAT SIGNATURE [xe] [privileged] [store]`, which adds an instruction or redefines a
+/// built-in one, or `remove MNEMONIC`. '#' starts a comment.
+pub fn load_instruction_set(lines: &[String]) -> AsmResult<()> {
+	let instructions = parse_instruction_set(lines)?;
+	if INSTRUCTION_SET.set(instructions).is_err() {
+		return Err(Diagnostic::error(0, "An instruction set extension was already loaded!"));
+	}
+	Ok(())
+}
+
+fn parse_instruction_set(lines: &[String]) -> AsmResult<Vec<Instruction>> {
+	let mut instructions = INSTRUCTIONS.to_vec();
+
+	for (index, line) in lines.iter().enumerate() {
+		let line_number = index + 1;
+		let fields: Vec<&str> = line.split('#').next().unwrap_or_default().split_whitespace().collect();
+
+		match fields.as_slice() {
+			[] => {}
+			["remove", mnemonic] => {
+				if !instructions.iter().any(|instruction| instruction.mnemonic == *mnemonic) {
+					return Err(Diagnostic::error(line_number, format!("Can't remove unknown instruction {}!", mnemonic)));
+				}
+				instructions.retain(|instruction| instruction.mnemonic != *mnemonic);
+			}
+			[mnemonic, opcode, format, signature, flags @ ..] => {
+				let instruction = parse_instruction(mnemonic, opcode, format, signature, flags, line_number)?;
+				match instructions.iter_mut().find(|other| other.mnemonic == instruction.mnemonic) {
+					Some(other) => *other = instruction,
+					None => instructions.push(instruction),
+				}
+			}
+			_ => {
+				return Err(Diagnostic::error(line_number, "Expected MNEMONIC OPCODE FORMAT SIGNATURE [xe] [privileged] [store] or remove MNEMONIC!"));
+			}
 		}
 	}
-	return false;
+
+	Ok(instructions)
+}
+
+fn parse_instruction(mnemonic: &str, opcode: &str, format: &str, signature: &str, flags: &[&str], line_number: usize) -> AsmResult<Instruction> {
+	let first = mnemonic.chars().next().unwrap_or_default();
+	if !first.is_ascii_uppercase() || !mnemonic.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+		return Err(Diagnostic::error(line_number, format!("Mnemonic {} must be uppercase letters and digits!", mnemonic)));
+	}
+	if is_directive(mnemonic) {
+		return Err(Diagnostic::error(line_number, format!("{} is a directive!", mnemonic)));
+	}
+
+	let opcode = match parse_numeric_literal(opcode, 16) {
+		Some(opcode) if (0..256).contains(&opcode) => opcode,
+		_ => return Err(Diagnostic::error(line_number, format!("Opcode {} must be a byte (hex by default)!", opcode))),
+	};
+
+	let formats = match format {
+		"1" => FORMAT_1,
+		"2" => FORMAT_2,
+		"3" => FORMAT_3,
+		"3/4" => FORMAT_3_4,
+		_ => return Err(Diagnostic::error(line_number, format!("Format must be one of: 1, 2, 3, 3/4, not {}!", format))),
+	};
+	// the n and i bits take the low two bits of the first byte
+	if formats[0] == 3 && opcode % 4 != 0 {
+		return Err(Diagnostic::error(line_number, format!("Format 3 opcode {:0>2X} must be a multiple of 4!", opcode)));
+	}
+
+	let signature_name = signature;
+	let signature = match signature {
+		"none" => OperandSignature::None,
+		"r1" => OperandSignature::Register,
+		"r1,r2" => OperandSignature::RegisterPair,
+		"r1,n" => OperandSignature::RegisterCount,
+		"n" => OperandSignature::Number,
+		"m" => OperandSignature::Memory,
+		_ => return Err(Diagnostic::error(line_number, format!("Signature must be one of: none, r1, r1,r2, r1,n, n, m, not {}!", signature))),
+	};
+	let allowed = match formats[0] {
+		1 => signature == OperandSignature::None,
+		2 => !matches!(signature, OperandSignature::None | OperandSignature::Memory),
+		_ => matches!(signature, OperandSignature::None | OperandSignature::Memory),
+	};
+	if !allowed {
+		return Err(Diagnostic::error(line_number, format!("Format {} instructions can't have the operand signature {}!", format, signature_name)));
+	}
+
+	let mut instruction = Instruction {
+		// the set lives until the end of the run
+		mnemonic: Box::leak(mnemonic.to_string().into_boxed_str()),
+		opcode,
+		formats,
+		signature,
+		xe_only: false,
+		privileged: false,
+		stores: false,
+	};
+	for flag in flags {
+		match *flag {
+			"xe" => instruction.xe_only = true,
+			"privileged" => instruction.privileged = true,
+			"store" => instruction.stores = true,
+			_ => return Err(Diagnostic::error(line_number, format!("Unknown flag {}, expected xe, privileged or store!", flag))),
+		}
+	}
+	if instruction.stores && signature != OperandSignature::Memory {
+		return Err(Diagnostic::error(line_number, "Only instructions with a memory operand can store!"));
+	}
+
+	Ok(instruction)
+}
+
+/// Looks up an instruction by mnemonic, with or without the format 4 '+'.
+pub fn get_instruction(str: &str) -> Option<&'static Instruction> {
+	let str = str.trim_start_matches("+");
+	instruction_set().iter().find(|instruction| instruction.mnemonic == str)
+}
+
+pub fn is_instruction(str: &str) -> bool {
+	get_instruction(str).is_some()
 }
 
 pub fn is_directive(str: &str) -> bool {
-	return DIRECTIVES.contains(&str);
+	DIRECTIVES.contains(&str)
 }
 
 pub fn get_instruction_format(opcode: &str) -> i32 {
-	let opcode_trimmed = opcode.trim_start_matches("+");
-
-	return if opcode.starts_with("+") {
-		4
-	} else if FORMAT_1.contains(&opcode_trimmed) {
-		1
-	} else if FORMAT_2.contains(&opcode_trimmed) {
-		2
-	} else {
-		3
+	if opcode.starts_with("+") {
+		return 4;
+	}
+	get_instruction(opcode).map(|instruction| instruction.formats[0]).unwrap_or(3)
+}
+
+pub fn get_instruction_hex(opcode: &str) -> i32 {
+	get_instruction(opcode).map(|instruction| instruction.opcode).unwrap_or(-1)
+}
+
+/// Whether an operation is followed by an operand field, anything after one that isn't is a comment.
+pub fn takes_operand(str: &str) -> bool {
+	match get_instruction(str) {
+		Some(instruction) => instruction.signature != OperandSignature::None,
+		None => str != "NOBASE",
+	}
+}
+
+/// Checks that an instruction's operand matches its signature and that format 4 is allowed.
+/// For `sic` the instruction and its addressing also have to exist on the original SIC machine.
+pub fn check_operand(operation: &str, operand: Option<&str>, sic: bool, line_number: usize) -> AsmResult<()> {
+	let instruction = match get_instruction(operation) {
+		Some(instruction) => instruction,
+		None => return Ok(()),
 	};
+
+	if sic {
+		if instruction.xe_only {
+			return Err(Diagnostic::error(line_number, format!("{} is a SIC/XE instruction!", instruction.mnemonic)));
+		} else if operation.starts_with("+") {
+			return Err(Diagnostic::error(line_number, "Format 4 is only available on SIC/XE!"));
+		} else if operand.is_some_and(|operand| operand.starts_with(['#', '@'])) {
+			return Err(Diagnostic::error(line_number, "Immediate and indirect addressing are only available on SIC/XE!"));
+		}
+	}
+
+	if operation.starts_with("+") && !instruction.formats.contains(&4) {
+		return Err(Diagnostic::error(line_number, format!("{} can't be used as format 4!", instruction.mnemonic)));
+	}
+
+	let fields = operand.map(split_operand_list).unwrap_or_default();
+	let (valid, expected) = match instruction.signature {
+		OperandSignature::None => (true, ""),
+		OperandSignature::Register => (fields.len() == 1, "a register"),
+		OperandSignature::RegisterPair => (fields.len() == 2, "two registers (r1,r2)"),
+		OperandSignature::RegisterCount => (fields.len() == 2, "a register and a count (r1,n)"),
+		OperandSignature::Number => (fields.len() == 1, "a number"),
+		OperandSignature::Memory => (is_memory_operand(&fields), "a memory operand (m or m,X)"),
+	};
+
+	if valid {
+		// the values of format 2 operands have their own messages
+		if instruction.formats.contains(&2) {
+			get_format_2_operands(instruction, &fields, line_number)?;
+		}
+		return Ok(());
+	}
+	Err(match operand {
+		Some(operand) => Diagnostic::error(line_number, format!("{} takes {}, not {}!", instruction.mnemonic, expected, operand)),
+		None => Diagnostic::error(line_number, format!("{} is missing its operand, it takes {}!", instruction.mnemonic, expected)),
+	})
 }
 
-pub fn get_instruction_hex(opcode: &String) -> i32 {
-	let opcode = opcode.trim_start_matches("+");
-	for (instruction, hex) in INSTRUCTIONS {
-		if *instruction == opcode {
-			return *hex;
+/// The r1 and r2 fields of a format 2 instruction: r2 is 0 for a single register, a shift count
+/// n is encoded as n-1 and SVC n puts n in r1.
+pub fn get_format_2_operands(instruction: &Instruction, fields: &[String], line_number: usize) -> AsmResult<(i32, i32)> {
+	let field = |index: usize| fields.get(index).map(|field| field.as_str()).unwrap_or_default();
+
+	match instruction.signature {
+		OperandSignature::Register => Ok((get_register_number(field(0), line_number)?, 0)),
+		OperandSignature::RegisterPair => Ok((get_register_number(field(0), line_number)?, get_register_number(field(1), line_number)?)),
+		OperandSignature::RegisterCount => {
+			let count = get_bounded_number(field(1), 1, 16, "Shift count", line_number)?;
+			Ok((get_register_number(field(0), line_number)?, count - 1))
 		}
+		OperandSignature::Number => Ok((get_bounded_number(field(0), 0, 15, "SVC number", line_number)?, 0)),
+		_ => Ok((0, 0)),
+	}
+}
+
+/// A register name, or a register number for machines with registers the names don't cover.
+pub fn get_register_number(register: &str, line_number: usize) -> AsmResult<i32> {
+	if let Some((_, number)) = REGISTERS.iter().find(|(name, _)| *name == register) {
+		return Ok(*number);
 	}
-	return -1;
-}
\ No newline at end of file
+
+	match parse_numeric_literal(register, 10) {
+		Some(number) if REGISTERS.iter().any(|(_, other)| *other == number) => Ok(number),
+		Some(number) => Err(Diagnostic::error(line_number, format!("There is no register number {}! Registers are 0-6, 8 and 9.", number))),
+		None => Err(Diagnostic::error(line_number, format!("Unknown register {}! Registers are A, X, L, B, S, T, F, PC and SW.", register))),
+	}
+}
+
+// a 4 bit number in r1 or r2, where the name describes it in errors
+fn get_bounded_number(field: &str, min: i32, max: i32, name: &str, line_number: usize) -> AsmResult<i32> {
+	match parse_numeric_literal(field, 10) {
+		Some(number) if (min..=max).contains(&number) => Ok(number),
+		Some(_) => Err(Diagnostic::error(line_number, format!("{} {} is out of range ({} to {})!", name, field, min, max))),
+		None => Err(Diagnostic::error(line_number, format!("{} {} is not a number!", name, field))),
+	}
+}
+
+// an address, #immediate or @indirect value, or an address indexed by X
+fn is_memory_operand(fields: &[String]) -> bool {
+	match fields {
+		[value] => !value.trim_start_matches(['#', '@']).is_empty(),
+		[value, index] => !value.is_empty() && !value.starts_with(['#', '@']) && index == "X",
+		_ => false,
+	}
+}
@@ -0,0 +1,108 @@
+use std::io;
+
+use crate::symbols::*;
+use crate::util::*;
+
+// Intermediate file layout (tab separated):
+//   H  name  start  length  first instruction
+//   S  symbol  address  REL/ABS  defining line  length
+//   L  location  label  opcode  operand  RELAX (or empty)  source line
+//   C  location  comment line
+// Label-only lines are L records with an empty opcode. The source line is last since it may contain tabs.
+
+pub fn write_intermediate_file(output_file: &str, symbol_table: &SymbolTable) -> io::Result<()> {
+	let mut records: Vec<String> = vec![];
+	records.push(format!("H\t{}\t{:0>6X}\t{:0>6X}\t{:0>6X}", symbol_table.program_name,
+	                     symbol_table.starting_memory_location,
+	                     symbol_table.total_memory_usage,
+	                     symbol_table.first_instruction.max(0)));
+
+	for symbol in symbol_table.symbols() {
+		records.push(format!("S\t{}\t{:0>6X}\t{}\t{}\t{}", symbol.name, symbol.memory_location,
+		                     if symbol.relative { "REL" } else { "ABS" }, symbol.line_number, symbol.length));
+	}
+
+	for (source_line, memory_location) in symbol_table.lines.iter().zip(&symbol_table.memory_locations) {
+		if source_line.operation.is_none() && source_line.label.is_none() {
+			records.push(format!("C\t{:0>6X}\t{}", memory_location, source_line.source));
+		} else {
+			records.push(format!("L\t{:0>6X}\t{}\t{}\t{}\t{}\t{}", memory_location,
+			                     source_line.label.as_deref().unwrap_or(""),
+			                     source_line.operation.as_deref().unwrap_or(""),
+			                     source_line.operand.as_deref().unwrap_or(""),
+			                     if source_line.relaxed { "RELAX" } else { "" },
+			                     source_line.source));
+		}
+	}
+
+	write_lines(output_file, records)
+}
+
+pub fn read_intermediate_file(records: &[String]) -> AsmResult<SymbolTable> {
+	let mut symbol_table = SymbolTable::new();
+
+	for (line_number, record) in records.iter().enumerate() {
+		let line_number = line_number + 1;
+		let fields: Vec<&str> = record.split('\t').collect();
+
+		match fields[0] {
+			"H" if fields.len() == 5 => {
+				symbol_table.program_name = fields[1].to_string();
+				symbol_table.starting_memory_location = parse_intermediate_hex(line_number, fields[2])?;
+				symbol_table.total_memory_usage = parse_intermediate_hex(line_number, fields[3])?;
+				symbol_table.first_instruction = parse_intermediate_hex(line_number, fields[4])?;
+			}
+			"S" if fields.len() == 6 => {
+				symbol_table.insert_symbol(Symbol {
+					name: fields[1].to_string(),
+					memory_location: parse_intermediate_hex(line_number, fields[2])?,
+					relative: fields[3] == "REL",
+					line_number: parse_intermediate_decimal(line_number, fields[4])? as usize,
+					length: parse_intermediate_decimal(line_number, fields[5])?,
+					block: None,
+					control_section: symbol_table.program_name.clone(),
+					external: false,
+				});
+			}
+			"L" if fields.len() >= 7 => {
+				symbol_table.memory_locations.push(parse_intermediate_hex(line_number, fields[1])?);
+				symbol_table.lines.push(SourceLine {
+					source: fields[6..].join("\t"),
+					label: Some(fields[2].to_string()).filter(|label| !label.is_empty()),
+					operation: Some(fields[3].to_string()).filter(|operation| !operation.is_empty()),
+					operand: Some(fields[4].to_string()).filter(|operand| !operand.is_empty()),
+					base: None,
+					relaxed: fields[5] == "RELAX",
+				});
+			}
+			"C" if fields.len() >= 3 => {
+				symbol_table.memory_locations.push(parse_intermediate_hex(line_number, fields[1])?);
+				symbol_table.lines.push(SourceLine {
+					source: fields[2..].join("\t"),
+					label: None,
+					operation: None,
+					operand: None,
+					base: None,
+					relaxed: false,
+				});
+			}
+			_ => return Err(Diagnostic::error(line_number, "Invalid intermediate file record!")),
+		}
+	}
+
+	if symbol_table.starting_memory_location == -1 {
+		return Err(Diagnostic::error(0, "Intermediate file has no header record!"));
+	}
+
+	track_base_directives(&mut symbol_table.lines);
+
+	Ok(symbol_table)
+}
+
+fn parse_intermediate_hex(line_number: usize, field: &str) -> AsmResult<i32> {
+	parse_str_i32_or_error(Some(field), 16, Diagnostic::error(line_number, "Invalid hex value in intermediate file!"))
+}
+
+fn parse_intermediate_decimal(line_number: usize, field: &str) -> AsmResult<i32> {
+	parse_str_i32_or_error(Some(field), 10, Diagnostic::error(line_number, "Invalid number in intermediate file!"))
+}
@@ -0,0 +1,65 @@
+use std::io;
+
+use serde_json::{json, Value};
+
+use crate::assembler::*;
+use crate::symbols::*;
+use crate::util::*;
+
+pub fn write_json_file(output_file: &str, symbol_table: &SymbolTable, object_code: &ObjectCode) -> io::Result<()> {
+	let symbols: Vec<Value> = symbol_table.symbols().iter().map(|symbol| json!({
+		"name": symbol.name,
+		"address": symbol.memory_location,
+		"relative": symbol.relative,
+		"line": symbol.line_number,
+		"length": symbol.length,
+		"block": symbol.block,
+		"control_section": symbol.control_section,
+		"external": symbol.external,
+	})).collect();
+
+	let lines: Vec<Value> = object_code.lines.iter().map(|line| {
+		let relocations: Vec<Value> = object_code.modifications.iter()
+			.filter(|modification| modification.line_number == line.line_number)
+			.map(|modification| json!({
+				"address": modification.address,
+				"half_bytes": modification.half_bytes,
+				"sign": modification.sign.to_string(),
+				"symbol": modification.symbol,
+			}))
+			.collect();
+
+		json!({
+			"line": line.line_number,
+			"source": line.source,
+			"address": line.address,
+			"format": line.format,
+			"addressing": line.addressing.map(addressing_to_json),
+			"relaxed": line.relaxed,
+			"object_code": hex::encode_upper(&line.code),
+			"relocations": relocations,
+		})
+	}).collect();
+
+	let dump = json!({
+		"program": {
+			"name": symbol_table.program_name,
+			"start_address": symbol_table.starting_memory_location,
+			"length": symbol_table.total_memory_usage,
+			"relocatable": symbol_table.is_relocatable(),
+			"entry_point": symbol_table.entry_point(),
+		},
+		"symbols": symbols,
+		"lines": lines,
+	});
+
+	write_lines(output_file, vec![serde_json::to_string_pretty(&dump).unwrap()])
+}
+
+fn addressing_to_json(addressing: Addressing) -> Value {
+	json!({
+		"mode": addressing.mode.name(),
+		"target": addressing.target.name(),
+		"indexed": addressing.indexed,
+	})
+}
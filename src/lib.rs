@@ -0,0 +1,14 @@
+pub mod util;
+pub mod instructions;
+pub mod symbols;
+pub mod assembler;
+pub mod scoff;
+pub mod image;
+pub mod ihex;
+pub mod srec;
+pub mod json;
+pub mod intermediate;
+pub mod listing;
+pub mod verify;
+pub mod lint;
+pub mod formatter;
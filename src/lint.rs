@@ -0,0 +1,174 @@
+use std::collections::BTreeSet;
+
+use crate::instructions::*;
+use crate::symbols::*;
+use crate::util::*;
+
+const DATA_DIRECTIVES: &[&str] = &["BYTE", "WORD", "RESB", "RESW"];
+
+/// Looks for common mistakes in a program after pass 1. Every problem is a warning, the
+/// program still assembles.
+pub fn lint_program(symbol_table: &SymbolTable) -> Vec<Diagnostic> {
+	let statements: Vec<usize> = (0..symbol_table.lines.len())
+		.filter(|index| symbol_table.lines[*index].operation.is_some())
+		.collect();
+
+	let mut problems: Vec<(usize, String)> = vec![];
+	problems.extend(check_unreferenced_labels(symbol_table));
+	problems.extend(check_unreachable_code(symbol_table, &statements));
+	problems.extend(check_unsaved_return_address(symbol_table, &statements));
+	problems.extend(check_device_polling(symbol_table, &statements));
+	problems.extend(check_self_modifying_code(symbol_table));
+	problems.extend(check_data_fall_through(symbol_table, &statements));
+
+	// report in source order
+	problems.sort_by_key(|(line_number, _)| *line_number);
+	problems.into_iter().map(|(line_number, problem)| Diagnostic::warning(line_number, problem)).collect()
+}
+
+// operation without a format 4 prefix, None for comments and label-only lines
+fn operation(symbol_table: &SymbolTable, index: usize) -> Option<&str> {
+	symbol_table.lines[index].operation.as_deref().map(|operation| operation.trim_start_matches("+"))
+}
+
+fn is_instruction_line(symbol_table: &SymbolTable, index: usize) -> bool {
+	operation(symbol_table, index).is_some_and(is_instruction)
+}
+
+fn is_unconditional_jump(symbol_table: &SymbolTable, index: usize) -> bool {
+	matches!(operation(symbol_table, index), Some("J") | Some("RSUB"))
+}
+
+// the symbol a memory operand refers to, without addressing prefixes or indexing
+fn operand_symbol(symbol_table: &SymbolTable, index: usize) -> Option<String> {
+	let operand = symbol_table.lines[index].operand.as_ref()?;
+	let operand = operand.trim_start_matches(['#', '@']);
+	let symbol = operand.split(',').next().unwrap();
+	Some(symbol.to_string()).filter(|symbol| symbol_table.contains_symbol(symbol))
+}
+
+fn check_unreferenced_labels(symbol_table: &SymbolTable) -> Vec<(usize, String)> {
+	let referenced: Vec<String> = symbol_table.lines.iter()
+		.filter_map(|line| line.operand.as_ref())
+		.flat_map(|operand| operand_symbols(operand))
+		.collect();
+
+	symbol_table.symbols().iter()
+		.filter(|symbol| !referenced.contains(&symbol.name))
+		// the START label names the program and doesn't need a reference
+		.filter(|symbol| symbol_table.lines[symbol.line_number - 1].operation.as_deref() != Some("START"))
+		.map(|symbol| (symbol.line_number, format!("Label {} is never referenced.", symbol.name)))
+		.collect()
+}
+
+fn check_unreachable_code(symbol_table: &SymbolTable, statements: &[usize]) -> Vec<(usize, String)> {
+	let mut problems = vec![];
+
+	for (index, next) in statements.iter().zip(statements.iter().skip(1)) {
+		if !is_unconditional_jump(symbol_table, *index) || !is_instruction_line(symbol_table, *next) {
+			continue;
+		}
+
+		// a label on the line, or on a label-only line before it, means something may jump there
+		let labeled = (*index + 1..=*next).any(|line| symbol_table.lines[line].label.is_some());
+		if !labeled {
+			problems.push((*next + 1, format!("Unreachable code after {} on line {}.",
+			                                 operation(symbol_table, *index).unwrap(), *index + 1)));
+		}
+	}
+
+	problems
+}
+
+fn check_unsaved_return_address(symbol_table: &SymbolTable, statements: &[usize]) -> Vec<(usize, String)> {
+	let mut problems = vec![];
+
+	// every JSUB target starts a subroutine that runs until its RSUB, and is checked once however
+	// many JSUBs call it
+	let entry_points: BTreeSet<usize> = statements.iter()
+		.filter(|index| operation(symbol_table, **index) == Some("JSUB"))
+		.filter_map(|index| operand_symbol(symbol_table, *index))
+		.filter_map(|symbol| symbol_table.get_symbol(&symbol).map(|symbol| symbol.line_number - 1))
+		.collect();
+
+	for entry_point in entry_points {
+		let mut saved = false;
+		let mut call: Option<usize> = None;
+
+		for index in statements.iter().filter(|index| **index >= entry_point) {
+			match operation(symbol_table, *index) {
+				Some("STL") => saved = true,
+				Some("RMO") if symbol_table.lines[*index].operand.as_deref().is_some_and(|operand| operand.starts_with("L,")) => saved = true,
+				Some("JSUB") if !saved && call.is_none() => call = Some(*index),
+				Some("RSUB") => {
+					if let Some(call) = call {
+						problems.push((*index + 1, format!("RSUB returns through L, which the JSUB on line {} overwrote without saving it (STL) first.",
+						                                   call + 1)));
+					}
+					break;
+				}
+				_ => {}
+			}
+		}
+	}
+
+	problems
+}
+
+fn check_device_polling(symbol_table: &SymbolTable, statements: &[usize]) -> Vec<(usize, String)> {
+	let mut problems = vec![];
+
+	for (position, index) in statements.iter().enumerate() {
+		if operation(symbol_table, *index) != Some("TD") {
+			continue;
+		}
+
+		let next_instruction = statements[position + 1..].iter().find(|next| is_instruction_line(symbol_table, **next));
+		if next_instruction.is_none_or(|next| operation(symbol_table, *next) != Some("JEQ")) {
+			problems.push((*index + 1, "TD is not followed by a JEQ polling the device until it is ready.".to_string()));
+		}
+	}
+
+	problems
+}
+
+fn check_self_modifying_code(symbol_table: &SymbolTable) -> Vec<(usize, String)> {
+	let mut problems = vec![];
+
+	for (index, line) in symbol_table.lines.iter().enumerate() {
+		let is_store = operation(symbol_table, index).and_then(get_instruction).is_some_and(|instruction| instruction.stores);
+		// immediate and indirect operands don't store at the symbol itself
+		if !is_store || line.operand.as_ref().is_some_and(|operand| operand.starts_with(['#', '@'])) {
+			continue;
+		}
+
+		if let Some(symbol) = operand_symbol(symbol_table, index) {
+			let defining_line = symbol_table.get_symbol(&symbol).unwrap().line_number;
+			if is_instruction_line(symbol_table, defining_line - 1) {
+				problems.push((index + 1, format!("{} stores into the instruction {} on line {}.",
+				                                  operation(symbol_table, index).unwrap(), symbol, defining_line)));
+			}
+		}
+	}
+
+	problems
+}
+
+fn check_data_fall_through(symbol_table: &SymbolTable, statements: &[usize]) -> Vec<(usize, String)> {
+	let mut problems = vec![];
+
+	for (position, index) in statements.iter().enumerate().skip(1) {
+		let previous = statements[position - 1];
+		let is_data = operation(symbol_table, *index).is_some_and(|operation| DATA_DIRECTIVES.contains(&operation));
+		if !is_data || !is_instruction_line(symbol_table, previous) || is_unconditional_jump(symbol_table, previous) {
+			continue;
+		}
+
+		// data after the last instruction is where it belongs
+		if statements[position..].iter().any(|next| is_instruction_line(symbol_table, *next)) {
+			problems.push((*index + 1, format!("Execution falls through from line {} into data.", previous + 1)));
+		}
+	}
+
+	problems
+}
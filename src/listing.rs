@@ -0,0 +1,67 @@
+use std::io;
+
+use crate::assembler::*;
+use crate::symbols::*;
+use crate::util::*;
+
+// bytes of object code shown per listing line, the rest continues on the following lines
+const CODE_BYTES_PER_LINE: usize = 4;
+
+pub fn write_listing_file(output_file: &str, symbol_table: &SymbolTable, object_code: &ObjectCode) -> io::Result<()> {
+	let mut listing: Vec<String> = vec![];
+	listing.push(format!("{: >5}  {: <6}  {: <8}  {: <5}  {}", "LINE", "LOC", "OBJECT", "ADDR", "SOURCE"));
+
+	for line in &object_code.lines {
+		let mut chunks = line.code.chunks(CODE_BYTES_PER_LINE);
+		let first_chunk = chunks.next().map(hex::encode_upper).unwrap_or_default();
+
+		listing.push(format!("{: >5}  {:0>6X}  {: <8}  {: <5}  {}", line.line_number, line.address, first_chunk,
+		                     addressing_label(line), line.source));
+
+		let mut current_memory_location = line.address + CODE_BYTES_PER_LINE as i32;
+		for chunk in chunks {
+			listing.push(format!("{: >5}  {:0>6X}  {}", "", current_memory_location, hex::encode_upper(chunk)));
+			current_memory_location += chunk.len() as i32;
+		}
+	}
+
+	listing.push(String::new());
+	listing.extend(cross_reference(symbol_table));
+
+	write_lines(output_file, listing)
+}
+
+// every symbol under its qualified name with the line defining it and the lines referring to it
+fn cross_reference(symbol_table: &SymbolTable) -> Vec<String> {
+	let width = symbol_table.symbols().iter().map(|symbol| symbol.name.len()).max().unwrap_or(0).max(6);
+
+	let mut cross_reference = vec![format!("{: <width$}  {: <6}  {: >7}  {}", "SYMBOL", "LOC", "DEFINED", "REFERENCES")];
+	for symbol in symbol_table.symbols() {
+		let references: Vec<String> = symbol_table.lines.iter().enumerate()
+			.filter(|(_, line)| line.operand.as_ref()
+				.is_some_and(|operand| operand_symbols(operand).contains(&symbol.name)))
+			.map(|(index, _)| (index + 1).to_string())
+			.collect();
+
+		cross_reference.push(format!("{: <width$}  {:0>6X}  {: >7}  {}", symbol.name, symbol.memory_location,
+		                             symbol.line_number, references.join(", ")).trim_end().to_string());
+	}
+	cross_reference
+}
+
+fn addressing_label(line: &AssembledLine) -> &str {
+	if line.relaxed {
+		return "RELAX";
+	} else if line.format == Some(4) {
+		return "EXT";
+	}
+
+	match line.addressing {
+		Some(addressing) => match addressing.target {
+			TargetAddressing::PcRelative => "PC",
+			TargetAddressing::BaseRelative => "BASE",
+			TargetAddressing::Direct => "DIR",
+		},
+		None => "",
+	}
+}
@@ -1,11 +1,56 @@
-mod util;
-mod instructions;
-mod symbols;
-mod scoff;
-
 use std::env;
+use std::path::Path;
 use std::process::exit;
 
+use sic_assembler_rust::image::MemoryImage;
+use sic_assembler_rust::util::AsmResult;
+use sic_assembler_rust::*;
+
+enum OutputFormat {
+    Scoff,
+    Binary,
+    IntelHex,
+    SRecord,
+    Json,
+}
+
+impl OutputFormat {
+    fn extension(&self) -> &str {
+        match self {
+            OutputFormat::Scoff => "obj",
+            OutputFormat::Binary => "bin",
+            OutputFormat::IntelHex => "hex",
+            OutputFormat::SRecord => "srec",
+            OutputFormat::Json => "json",
+        }
+    }
+}
+
+struct Options {
+    filenames: Vec<String>,
+    output: Option<String>,
+    format: OutputFormat,
+    load_address: Option<i32>,
+    /// Assembles at this address instead of the START operand
+    origin: Option<i32>,
+    fill: u8,
+    write_intermediate: bool,
+    from_intermediate: bool,
+    write_listing: bool,
+    relax: bool,
+    strict: bool,
+    /// None detects the source format from the file
+    fixed_columns: Option<bool>,
+    print_symbols: bool,
+    long_names: bool,
+    sic: bool,
+    /// fmt only reports files that aren't formatted instead of rewriting them
+    check: bool,
+    indent_with_tabs: bool,
+    /// Instruction set extension applied before anything is read
+    instructions: Option<String>,
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
@@ -14,11 +59,428 @@ fn main() {
         exit(0);
     }
 
-    let filename = &args[1];
+    if args[1] == "verify" {
+        if args.len() < 3 {
+            println!("Please specify an object file to verify!");
+            exit(1);
+        }
+        verify(&args[2]);
+        return;
+    }
+
+    let command = args[1].as_str();
+    let options = parse_options(&args[if command == "lint" || command == "fmt" { 2 } else { 1 }..]);
+
+    if let Some(filename) = &options.instructions {
+        let lines = util::read_source(filename).unwrap_or_else(|error| {
+            eprintln!("Could not read {}: {}", filename, error);
+            exit(1);
+        });
+        if let Err(error) = instructions::load_instruction_set(&lines) {
+            eprintln!("{}: {}", filename, error);
+            exit(1);
+        }
+    }
+
+    let mut failed = false;
+    for filename in &options.filenames {
+        let succeeded = match command {
+            "lint" => lint(filename, &options),
+            "fmt" => format_file(filename, &options),
+            _ => assemble_file(filename, &options),
+        };
+        if !succeeded {
+            failed = true;
+        }
+    }
+
+    if failed {
+        exit(1);
+    }
+}
+
+fn assemble_file(filename: &str, options: &Options) -> bool {
+    // outputs are named after the source, so pass 2 from "prog.sic.int" writes "prog.sic.obj", and
+    // source from stdin names its intermediate file and listing after -o ("prog.obj" gives "prog.int")
+    let base_name = if filename == "-" {
+        match options.output.as_deref().filter(|output| *output != "-") {
+            Some(output) => Path::new(output).with_extension("").to_string_lossy().into_owned(),
+            None if options.write_intermediate || options.write_listing => {
+                eprintln!("Intermediate files and listings of stdin need an output file to be named after (-o)!");
+                return false;
+            }
+            None => filename.to_string(),
+        }
+    } else if options.from_intermediate {
+        filename.strip_suffix(".int").unwrap_or(filename).to_string()
+    } else {
+        filename.to_string()
+    };
 
-    let mut symbol_table = symbols::SymbolTable::new();
-    symbol_table.parse_symbol_table(filename);
-    // symbol_table.print_symbol_table();
+    let lines = match util::read_source(filename) {
+        Ok(lines) => lines,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", filename, error);
+            return false;
+        }
+    };
+    let mut symbol_table = match read_symbol_table(&lines, options) {
+        Ok(symbol_table) => symbol_table,
+        Err(error) => {
+            eprintln!("{}", error);
+            return false;
+        }
+    };
+
+    if options.print_symbols {
+        symbol_table.print_symbol_table();
+    }
+
+    for warning in &symbol_table.warnings {
+        eprintln!("{}", warning);
+    }
+
+    if options.write_intermediate && !options.from_intermediate {
+        let intermediate_file = format!("{}.int", base_name);
+        if let Err(error) = intermediate::write_intermediate_file(&intermediate_file, &symbol_table) {
+            eprintln!("Could not write to {}: {}", intermediate_file, error);
+            return false;
+        }
+    }
+
+    let object_code = match assembler::assemble(&mut symbol_table) {
+        Ok(object_code) => object_code,
+        Err(error) => {
+            eprintln!("{}", error);
+            return false;
+        }
+    };
+
+    for warning in &object_code.warnings {
+        eprintln!("{}", warning);
+    }
+
+    if options.write_listing {
+        let listing_file = format!("{}.lst", base_name);
+        if let Err(error) = listing::write_listing_file(&listing_file, &symbol_table, &object_code) {
+            eprintln!("Could not write to {}: {}", listing_file, error);
+            return false;
+        }
+    }
+
+    // source read from stdin is written to stdout unless an output file is given
+    let output_file = match &options.output {
+        Some(output) => output.clone(),
+        None if filename == "-" => "-".to_string(),
+        None => format!("{}.{}", base_name, options.format.extension()),
+    };
+
+    // images of absolute programs can't be moved, they have to be assembled at the new address
+    let uses_load_address = !matches!(options.format, OutputFormat::Scoff | OutputFormat::Json);
+    if uses_load_address && !symbol_table.is_relocatable()
+        && options.load_address.is_some_and(|address| address != symbol_table.starting_memory_location) {
+        eprintln!("{} is absolute (START {:X}) and can't be loaded at another address! Use --origin to assemble it there.",
+                  filename, symbol_table.starting_memory_location);
+        return false;
+    }
+
+    let load_address = options.load_address.unwrap_or(symbol_table.starting_memory_location);
+    let result = match options.format {
+        OutputFormat::Scoff => {
+            scoff::write_object_file(&output_file, &symbol_table, &object_code)
+        }
+        OutputFormat::Binary => {
+            let image = MemoryImage::new(&symbol_table, &object_code, load_address, options.fill);
+            image::write_binary_file(&output_file, &image)
+        }
+        OutputFormat::IntelHex => {
+            let image = MemoryImage::new(&symbol_table, &object_code, load_address, options.fill);
+            ihex::write_hex_file(&output_file, &image)
+        }
+        OutputFormat::SRecord => {
+            let image = MemoryImage::new(&symbol_table, &object_code, load_address, options.fill);
+            srec::write_srec_file(&output_file, &symbol_table, &image)
+        }
+        OutputFormat::Json => {
+            json::write_json_file(&output_file, &symbol_table, &object_code)
+        }
+    };
+
+    if let Err(error) = result {
+        eprintln!("Could not write to {}: {}", output_file, error);
+        return false;
+    }
+    true
+}
 
-    scoff::write_object_file(filename, symbol_table);
+// pass 1, or the symbol table read back from an intermediate file
+fn read_symbol_table(lines: &[String], options: &Options) -> AsmResult<symbols::SymbolTable> {
+    let source_format = symbols::SourceFormat {
+        strict: options.strict,
+        fixed_columns: options.fixed_columns.unwrap_or_else(|| symbols::detect_fixed_columns(lines)),
+        long_names: options.long_names,
+        sic: options.sic,
+        origin: options.origin,
+    };
+
+    if options.from_intermediate {
+        // the machine isn't part of the intermediate file, so pass 2 takes it from the options
+        let mut symbol_table = intermediate::read_intermediate_file(lines)?;
+        symbol_table.source_format.sic = options.sic;
+        Ok(symbol_table)
+    } else if options.relax {
+        assembler::relax_symbol_table(lines, source_format)
+    } else {
+        let mut symbol_table = symbols::SymbolTable::new();
+        symbol_table.source_format = source_format;
+        symbol_table.parse_symbol_table(lines)?;
+        Ok(symbol_table)
+    }
+}
+
+fn lint(filename: &str, options: &Options) -> bool {
+    let lines = match util::read_source(filename) {
+        Ok(lines) => lines,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", filename, error);
+            return false;
+        }
+    };
+    let symbol_table = match read_symbol_table(&lines, options) {
+        Ok(symbol_table) => symbol_table,
+        Err(error) => {
+            eprintln!("{}", error);
+            return false;
+        }
+    };
+
+    let mut problems = symbol_table.warnings.clone();
+    problems.extend(lint::lint_program(&symbol_table));
+
+    if problems.is_empty() {
+        println!("{}: OK", filename);
+        return true;
+    }
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    println!("{}: {} problem(s) found", filename, problems.len());
+    false
+}
+
+fn format_file(filename: &str, options: &Options) -> bool {
+    let lines = match util::read_source(filename) {
+        Ok(lines) => lines,
+        Err(error) => {
+            eprintln!("Could not read {}: {}", filename, error);
+            return false;
+        }
+    };
+    let formatted = formatter::format_source(&lines, options.indent_with_tabs);
+
+    if options.check {
+        if formatted != lines {
+            println!("{}: not formatted", filename);
+            return false;
+        }
+        return true;
+    }
+
+    // formatted in place, or to stdout for stdin
+    let mut source = formatted.join("\n");
+    source.push('\n');
+    if let Err(error) = util::write_bytes(filename, source.as_bytes()) {
+        eprintln!("Could not write to {}: {}", filename, error);
+        return false;
+    }
+    true
+}
+
+fn parse_options(args: &[String]) -> Options {
+    let mut filenames: Vec<String> = vec![];
+    let mut output = None;
+    let mut format = OutputFormat::Scoff;
+    let mut load_address = None;
+    let mut origin = None;
+    let mut fill = 0;
+    let mut write_intermediate = false;
+    let mut from_intermediate = false;
+    let mut write_listing = false;
+    let mut relax = false;
+    let mut strict = false;
+    let mut fixed_columns = None;
+    let mut print_symbols = false;
+    let mut long_names = false;
+    let mut sic = false;
+    let mut check = false;
+    let mut indent_with_tabs = true;
+    let mut instructions = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                match args.next() {
+                    Some(path) => output = Some(path.clone()),
+                    None => {
+                        eprintln!("Please specify an output file after {}!", arg);
+                        exit(1);
+                    }
+                }
+            }
+            "-i" | "--intermediate" => {
+                write_intermediate = true;
+            }
+            "--from-intermediate" => {
+                from_intermediate = true;
+            }
+            "-l" | "--listing" => {
+                write_listing = true;
+            }
+            "--relax" => {
+                relax = true;
+            }
+            "--strict" => {
+                strict = true;
+            }
+            "--fixed-columns" => {
+                fixed_columns = Some(true);
+            }
+            "--free-form" => {
+                fixed_columns = Some(false);
+            }
+            "-s" | "--symbols" => {
+                print_symbols = true;
+            }
+            "--long-names" => {
+                long_names = true;
+            }
+            "--instructions" => {
+                match args.next() {
+                    Some(path) => instructions = Some(path.clone()),
+                    None => {
+                        eprintln!("Please specify an instruction set file after {}!", arg);
+                        exit(1);
+                    }
+                }
+            }
+            "--sic" => {
+                sic = true;
+            }
+            "--check" => {
+                check = true;
+            }
+            "--indent" => {
+                indent_with_tabs = match args.next().map(|s| s.as_str()) {
+                    Some("tabs") => true,
+                    Some("spaces") => false,
+                    _ => {
+                        eprintln!("Indent must be one of: tabs, spaces");
+                        exit(1);
+                    }
+                };
+            }
+            "-f" | "--format" => {
+                format = match args.next().map(|s| s.as_str()) {
+                    Some("scoff") | Some("obj") => OutputFormat::Scoff,
+                    Some("binary") | Some("bin") => OutputFormat::Binary,
+                    Some("ihex") | Some("hex") => OutputFormat::IntelHex,
+                    Some("srec") => OutputFormat::SRecord,
+                    Some("json") => OutputFormat::Json,
+                    _ => {
+                        eprintln!("Output format must be one of: scoff, binary, ihex, srec, json");
+                        exit(1);
+                    }
+                };
+            }
+            "--load-address" => {
+                let address = parse_option_number(args.next(), "Load address must be a number (hex by default)!");
+                if !(0..1048576).contains(&address) {
+                    eprintln!("Load address must be inside SIC memory (0-FFFFF)!");
+                    exit(1);
+                }
+                load_address = Some(address);
+            }
+            "--origin" => {
+                let address = parse_option_number(args.next(), "Origin must be a number (hex by default)!");
+                if !(0..1048576).contains(&address) {
+                    eprintln!("Origin must be inside SIC memory (0-FFFFF)!");
+                    exit(1);
+                }
+                origin = Some(address);
+            }
+            "--fill" => {
+                let byte = parse_option_number(args.next(), "Fill byte must be a number (hex by default)!");
+                if !(0..256).contains(&byte) {
+                    eprintln!("Fill byte must be between 00 and FF!");
+                    exit(1);
+                }
+                fill = byte as u8;
+            }
+            _ => {
+                if arg.starts_with("-") && arg != "-" {
+                    eprintln!("Unknown option: {}", arg);
+                    exit(1);
+                }
+                filenames.push(arg.clone());
+            }
+        }
+    }
+
+    if filenames.is_empty() {
+        println!("Please specify a SIC source file to assemble!");
+        exit(0);
+    }
+
+    if output.is_some() && filenames.len() > 1 {
+        eprintln!("-o can only be used when assembling a single source file!");
+        exit(1);
+    }
+
+    Options {
+        filenames,
+        output,
+        format,
+        load_address,
+        origin,
+        fill,
+        write_intermediate,
+        from_intermediate,
+        write_listing,
+        relax,
+        strict,
+        fixed_columns,
+        print_symbols,
+        long_names,
+        sic,
+        check,
+        indent_with_tabs,
+        instructions,
+    }
+}
+
+fn parse_option_number(value: Option<&String>, error_message: &str) -> i32 {
+    match value.and_then(|value| util::parse_numeric_literal(value, 16)) {
+        Some(number) => number,
+        None => {
+            eprintln!("{}", error_message);
+            exit(1);
+        }
+    }
+}
+
+fn verify(filename: &str) {
+    let problems = verify::verify_object_file(filename);
+
+    if problems.is_empty() {
+        println!("{}: OK", filename);
+        return;
+    }
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+    println!("{}: {} problem(s) found", filename, problems.len());
+    exit(1);
 }
@@ -0,0 +1,41 @@
+use std::io;
+
+use crate::image::*;
+use crate::symbols::*;
+use crate::util::*;
+
+pub fn write_srec_file(output_file: &str, symbol_table: &SymbolTable, image: &MemoryImage) -> io::Result<()> {
+	let mut records: Vec<String> = vec![];
+
+	// programs that fit in 64K use 16 bit addresses (S1/S9), everything else 24 bit (S2/S8)
+	let wide = image.load_address + image.bytes.len() as i32 > 0x10000;
+	let address_bytes = if wide { 3 } else { 2 };
+
+	records.push(srec_record(0, 0, 2, symbol_table.program_name.as_bytes()));
+
+	let chunks = image.chunks(16);
+	for (address, bytes) in &chunks {
+		let record_type = if wide { 2 } else { 1 };
+		records.push(srec_record(record_type, *address, address_bytes, bytes));
+	}
+
+	records.push(srec_record(5, chunks.len() as i32, 2, &[]));
+
+	let end_type = if wide { 8 } else { 9 };
+	records.push(srec_record(end_type, image.entry_point, address_bytes, &[]));
+
+	write_lines(output_file, records)
+}
+
+fn srec_record(record_type: u8, address: i32, address_bytes: usize, data: &[u8]) -> String {
+	let mut bytes = vec![(address_bytes + data.len() + 1) as u8];
+	for index in (0..address_bytes).rev() {
+		bytes.push((address >> (index * 8)) as u8);
+	}
+	bytes.extend_from_slice(data);
+
+	let sum = bytes.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+	bytes.push(!sum);
+
+	format!("S{}{}", record_type, hex::encode_upper(bytes))
+}
@@ -1,4 +1,4 @@
-use std::process::exit;
+use std::collections::HashMap;
 
 use crate::instructions::*;
 use crate::util::*;
@@ -6,145 +6,322 @@ use crate::util::*;
 pub struct Symbol {
 	pub name: String,
 	pub memory_location: i32,
+	/// Relative symbols move with the program when it is relocated, absolute ones don't. Every label
+	/// of a relocatable program is relative and every label of an absolute one is absolute.
+	pub relative: bool,
+	/// Source line the symbol is defined on
+	pub line_number: usize,
+	/// Bytes taken by the statement defining the symbol, e.g. 3 for a WORD or n for RESB n
+	pub length: i32,
+	/// Program block the symbol belongs to. Always None, the default block, since USE isn't supported.
+	pub block: Option<String>,
+	pub control_section: String,
+	/// Defined in another control section and resolved by the loader. Always false, since EXTREF
+	/// isn't supported.
+	pub external: bool,
+}
+
+/// A source line as split up by pass 1. Comment lines have no label, operation or operand,
+/// and lines holding only a label have no operation.
+pub struct SourceLine {
+	pub source: String,
+	pub label: Option<String>,
+	pub operation: Option<String>,
+	pub operand: Option<String>,
+	/// Operand of the BASE directive in effect for this line, if any.
+	pub base: Option<String>,
+	/// Promoted from format 3 to format 4 by relaxation
+	pub relaxed: bool,
 }
 
 pub struct SymbolTable {
-	pub symbols: Vec<Symbol>,
+	/// Symbols in definition order, indexed by name in symbol_indexes
+	symbols: Vec<Symbol>,
+	symbol_indexes: HashMap<String, usize>,
+	pub lines: Vec<SourceLine>,
 	pub memory_locations: Vec<i32>,
+	/// Line numbers of format 3 instructions that pass 1 should size as format 4
+	pub relaxed_lines: Vec<usize>,
 	pub starting_memory_location: i32,
 	pub first_instruction: i32,
 	pub total_memory_usage: i32,
-	pub base_location: i32,
 	pub program_name: String,
+	pub source_format: SourceFormat,
+	/// Pass 1 warnings, printed once after the table is final
+	pub warnings: Vec<Diagnostic>,
+	/// Last global label, which .LOCAL labels are scoped to
+	local_scope: String,
+	/// Line number, digit and qualified name of every numeric nH label in order
+	numeric_labels: Vec<(usize, char, String)>,
+}
+
+impl Default for SymbolTable {
+	fn default() -> Self {
+		SymbolTable::new()
+	}
+}
+
+/// How pass 1 reads source lines, which machine they are assembled for and where.
+#[derive(Clone, Copy, Default)]
+pub struct SourceFormat {
+	/// Only '#' comments, no blank lines and fixed field counts, as before '.' comments were supported
+	pub strict: bool,
+	/// Label in columns 1-8, opcode in 10-15 and operand in 17-35 instead of whitespace separated fields
+	pub fixed_columns: bool,
+	/// Allow symbol names of up to 32 characters, truncated to 6 in object records
+	pub long_names: bool,
+	/// Assemble for the original SIC machine: no SIC/XE instructions, format 4 or immediate and
+	/// indirect addressing, and 15 bit direct addresses
+	pub sic: bool,
+	/// Assemble at this address instead of the START operand
+	pub origin: Option<i32>,
 }
 
+/// Longest symbol name allowed with and without long names.
+const MAX_SYMBOL_LENGTH: usize = 6;
+const MAX_LONG_SYMBOL_LENGTH: usize = 32;
+
 impl SymbolTable {
 	pub fn new() -> SymbolTable {
 		SymbolTable {
 			symbols: vec![],
+			symbol_indexes: HashMap::new(),
+			lines: vec![],
 			memory_locations: vec![],
+			relaxed_lines: vec![],
 			starting_memory_location: -1,
 			first_instruction: -1,
 			total_memory_usage: -1,
-			base_location: -1,
 			program_name: "".to_string(),
+			source_format: SourceFormat::default(),
+			warnings: vec![],
+			local_scope: String::new(),
+			numeric_labels: vec![],
 		}
 	}
 
-	pub fn parse_symbol_table(&mut self, filename: &str) {
-		if let Ok(lines) = read_lines(filename) {
-			let mut line_number: i32 = 0;
-			let mut current_memory_location: i32 = 0;
+	pub fn parse_symbol_table(&mut self, lines: &[String]) -> AsmResult<()> {
+		let mut line_number: usize = 0;
+		let mut current_memory_location: i32 = 0;
 
-			for line in lines {
-				if let Ok(line_str) = line {
-					self.memory_locations.push(current_memory_location);
+		for line_str in lines {
+			self.memory_locations.push(current_memory_location);
 
-					line_number += 1;
-					self.parse_line(line_str, line_number, &mut current_memory_location);
-				}
-			}
+			line_number += 1;
+			self.parse_line(line_str.clone(), line_number, &mut current_memory_location)?;
+		}
 
-			if current_memory_location > 1048576 {
-				println!("Error (line {}): SIC memory exceeded!", line_number);
-				exit(1);
-			}
+		if self.starting_memory_location == -1 {
+			return Err(Diagnostic::error(line_number, "No START directive found!"));
+		}
 
-			if self.starting_memory_location == -1 {
-				println!("Error (line {}): No START directive found!", line_number);
-				exit(1);
-			}
+		if let Some(origin) = self.source_format.origin {
+			self.starting_memory_location = origin;
+		}
+
+		if self.starting_memory_location + current_memory_location > 1048576 {
+			return Err(Diagnostic::error(line_number, "SIC memory exceeded!"));
+		}
 
-			self.total_memory_usage = current_memory_location;
+		self.total_memory_usage = current_memory_location;
 
-			for symbol in &mut self.symbols {
-				symbol.memory_location += self.starting_memory_location;
-			}
+		// a program is a single control section, so every symbol belongs to it and moves with it
+		let relative = self.is_relocatable();
+		for symbol in &mut self.symbols {
+			symbol.memory_location += self.starting_memory_location;
+			symbol.relative = relative;
+			symbol.control_section = self.program_name.clone();
+		}
+
+		for memory_location in &mut self.memory_locations {
+			*memory_location += self.starting_memory_location;
 		}
+
+		self.resolve_local_references()?;
+		track_base_directives(&mut self.lines);
+
+		Ok(())
+	}
+
+	/// Every symbol in definition order.
+	pub fn symbols(&self) -> &[Symbol] {
+		&self.symbols
 	}
 
 	pub fn contains_symbol(&self, name: &str) -> bool {
-		for symbol in &self.symbols {
-			if symbol.name == name {
-				return true;
-			}
+		self.symbol_indexes.contains_key(name)
+	}
+
+	pub fn get_symbol(&self, name: &str) -> Option<&Symbol> {
+		self.symbol_indexes.get(name).map(|index| &self.symbols[*index])
+	}
+
+	pub fn get_symbol_location(&self, name: &str) -> i32 {
+		match self.get_symbol(name) {
+			Some(symbol) => symbol.memory_location,
+			None => -1,
 		}
-		return false;
 	}
 
-	pub fn get_symbol_location(&self, name: &String) -> i32 {
-		for symbol in &self.symbols {
-			if symbol.name == *name {
-				return symbol.memory_location;
+	/// Adds a symbol without any of the checks done for labels in the source.
+	pub fn insert_symbol(&mut self, symbol: Symbol) {
+		self.symbol_indexes.insert(symbol.name.clone(), self.symbols.len());
+		self.symbols.push(symbol);
+	}
+
+	/// Evaluates a WORD style expression of numbers and symbols joined by + and -.
+	/// Returns the value and the relocation count: 0 for absolute, 1 for relocatable,
+	/// -1 when the program start has to be subtracted.
+	pub fn evaluate_expression(&self, expression: &str, line_number: usize) -> AsmResult<(i32, i32)> {
+		let mut value = 0;
+		let mut relocation = 0;
+
+		for (sign, term) in split_expression_terms(expression) {
+			if term.is_empty() {
+				return Err(Diagnostic::error(line_number, format!("Invalid expression {}!", expression)));
 			}
+
+			let term_value = match parse_numeric_literal(&term, 10) {
+				Some(number) => number,
+				None => {
+					if !self.contains_symbol(&term) {
+						return Err(Diagnostic::error(line_number, format!("Undefined symbol {}!", term)));
+					}
+					relocation += sign;
+					self.get_symbol_location(&term)
+				}
+			};
+			value += sign * term_value;
+		}
+
+		Ok((value, relocation))
+	}
+
+	/// Programs assembled at address 0 can be loaded anywhere and get modification records, any
+	/// other start address makes the program absolute. SIC programs are always absolute, since they
+	/// are loaded where they were assembled.
+	pub fn is_relocatable(&self) -> bool {
+		self.starting_memory_location == 0 && !self.source_format.sic
+	}
+
+	/// Where execution starts: the symbol given to END, or the first instruction when END has no operand.
+	pub fn entry_point(&self) -> i32 {
+		let end_operand = self.lines.iter()
+			.find(|line| line.operation.as_deref() == Some("END"))
+			.and_then(|line| line.operand.as_ref());
+
+		match end_operand.map(|operand| self.get_symbol_location(operand)) {
+			Some(location) if location != -1 => location,
+			_ => self.starting_memory_location + self.first_instruction.max(0),
 		}
-		return -1;
 	}
 
 	pub fn print_symbol_table(&self) {
+		// local labels have longer qualified names
+		let width = self.symbols.iter().map(|symbol| symbol.name.len()).max().unwrap_or(0).max(6);
+
+		println!("{: <width$}  {: <6}  {: <4}  {: >5}  {: >6}  {: <9}  CSECT", "NAME", "LOC", "TYPE", "LINE", "LENGTH", "BLOCK");
 		for symbol in &self.symbols {
-			println!("{: >6}\t{:X}", symbol.name, symbol.memory_location);
+			println!("{: <width$}  {:0>6X}  {: <4}  {: >5}  {: >6}  {: <9}  {}{}", symbol.name, symbol.memory_location,
+			         if symbol.relative { "REL" } else { "ABS" }, symbol.line_number, symbol.length,
+			         symbol.block.as_deref().unwrap_or("(default)"), symbol.control_section,
+			         if symbol.external { " EXT" } else { "" });
 		}
 	}
 
-	fn parse_line(&mut self, line: String, line_number: i32, current_memory_location: &mut i32) {
-		// ignore comments
-		if line.starts_with("#") {
-			return;
+	fn parse_line(&mut self, line: String, line_number: usize, current_memory_location: &mut i32) -> AsmResult<()> {
+		let mut source_line = SourceLine {
+			source: line.clone(),
+			label: None,
+			operation: None,
+			operand: None,
+			base: None,
+			relaxed: false,
+		};
+
+		let strict = self.source_format.strict;
+
+		// ignore comments, and outside strict mode also '.' comments and blank lines
+		if line.starts_with("#") || (!strict && (is_dot_comment(&line) || line.trim().is_empty())) {
+			self.lines.push(source_line);
+			return Ok(());
 		}
 
-		// println!("{}", line);
-		let split: Vec<String> = sic_line_to_vector(line);
+		let (label, operation, operand) = if self.source_format.fixed_columns {
+			split_fixed_columns(&line, line_number)?
+		} else {
+			// println!("{}", line);
+			let split: Vec<String> = sic_line_to_vector(line.clone(), strict);
 
-		if split.len() == 0 {
-			println!("Error (line {}): Empty line! Not allowed in SIC. Use comments instead (#)", line_number);
-			exit(1);
-		} else if split.len() == 1 {
-			let str1 = split.get(0).unwrap();
-			if !is_instruction(str1) {
-				println!("Error (line {}): Not an instruction!", line_number);
-				exit(1);
+			if split.is_empty() {
+				return Err(Diagnostic::error(line_number, "Empty line! Not allowed in SIC. Use comments instead (#)"));
 			}
 
-			self.handle_instruction(current_memory_location, str1);
-		} else if split.len() == 2 {
-			let str1 = split.get(0).unwrap();
-			let str2 = split.get(1).unwrap();
-
-			if is_instruction(str1) {
-				self.handle_instruction(current_memory_location, str1);
-			} else if is_instruction(str2) {
-				self.add_symbol(line_number, str1, *current_memory_location);
-				self.handle_instruction(current_memory_location, str2);
-			} else if is_directive(str1) {
-				self.handle_directive(line_number, current_memory_location, str1, Some(str2))
-			} else if is_directive(str2) {
-				self.add_symbol(line_number, str1, *current_memory_location);
-				self.handle_directive(line_number, current_memory_location, str2, None);
+			if strict {
+				split_fields_strict(&split, line_number)?
 			} else {
-				println!("Error (line {}): Invalid line! Not an instruction or directive!", line_number);
-				exit(1);
+				split_fields(&line, &split, line_number)?
 			}
+		};
+
+		// local labels are stored under their qualified name, without the colon of .LOCAL:
+		let label = match label {
+			Some(label) => {
+				let label = match local_label_definition(&label) {
+					Some(local_name) => format!(".{}", local_name),
+					None => label,
+				};
+				if is_instruction(&label) || is_directive(&label) {
+					self.warnings.push(Diagnostic::warning(line_number, format!("Label {} has the same name as an operation!", label)));
+				}
+				Some(self.add_symbol(line_number, &label, *current_memory_location)?)
+			}
+			None => None,
+		};
+
+		// a line with only a label binds it to the address of the next statement
+		let operation = match operation {
+			Some(operation) => operation,
+			None => {
+				source_line.label = label;
+				self.lines.push(source_line);
+				return Ok(());
+			}
+		};
+
+		let statement_location = *current_memory_location;
+		if is_instruction(&operation) {
+			check_operand(&operation, operand.as_deref(), self.source_format.sic, line_number)?;
+			self.handle_instruction(current_memory_location, &operation);
 		} else {
-			let str1 = split.get(0).unwrap();
-			let str2 = split.get(1).unwrap();
-			let str3 = split.get(2).unwrap();
-
-			if is_instruction(str2) {
-				self.add_symbol(line_number, str1, *current_memory_location);
-				self.handle_instruction(current_memory_location, str2);
-			} else if is_directive(str2) {
-				self.add_symbol(line_number, str1, *current_memory_location);
-				self.handle_directive(line_number, current_memory_location, str2, Some(str3));
-
-				if str2 == "START" {
-					self.program_name = str2.clone();
+			self.handle_directive(line_number, current_memory_location, &operation, operand.as_deref())?;
+
+			if operation == "START" {
+				if let Some(label) = &label {
+					self.program_name = label.clone();
 				}
-			} else {
-				println!("Error (line {}): Invalid line! Not an instruction or directive!", line_number);
-				exit(1);
 			}
 		}
+
+		source_line.label = label;
+		source_line.operation = Some(operation);
+		source_line.operand = operand;
+
+		// relaxed instructions are rewritten as format 4 and take one more byte
+		if self.relaxed_lines.contains(&(line_number)) {
+			let operation = source_line.operation.as_ref().unwrap();
+			source_line.operation = Some(format!("+{}", operation));
+			source_line.relaxed = true;
+			*current_memory_location += 1;
+		}
+
+		if let Some(label) = &source_line.label {
+			let index = self.symbol_indexes[label];
+			self.symbols[index].length = *current_memory_location - statement_location;
+		}
+
+		self.lines.push(source_line);
+		Ok(())
 	}
 
 	fn handle_instruction(&mut self, current_memory_location: &mut i32, instruction: &str) {
@@ -154,47 +331,43 @@ impl SymbolTable {
 		*current_memory_location += get_instruction_format(instruction);
 	}
 
-	fn handle_directive(&mut self, line_number: i32, current_memory_location: &mut i32, directive: &str, operand: Option<&str>) {
+	fn handle_directive(&mut self, line_number: usize, current_memory_location: &mut i32, directive: &str, operand: Option<&str>) -> AsmResult<()> {
 		match directive {
 			"START" => {
-				let location = parse_str_i32_or_error(operand, 16, format!("error (line {}): invalid or no operand provided for directive.", line_number));
+				// START keeps the SIC convention of a hex operand when no radix is given
+				let location = parse_number_or_error(operand, 16, NumericRange::Address, line_number)?;
 				self.starting_memory_location = location;
 			}
 			"BYTE" => {
 				if operand.is_none() {
-					println!("Error (line {}): Invalid or no operand provided for directive.", line_number);
-					exit(1);
-				}
-				let operand_string = String::from(operand.unwrap());
-				if operand_string.starts_with("C'") && operand_string.ends_with("'") {
-					let stripped = operand_string.strip_prefix("C'").unwrap().strip_suffix("'").unwrap();
-					let num_bytes: i32 = stripped.len() as i32;
-					*current_memory_location += num_bytes;
-				} else if operand_string.starts_with("X'") && operand_string.ends_with("'") {
-					let stripped = operand_string.strip_prefix("X'").unwrap().strip_suffix("'").unwrap();
-					let num_bytes: i32 = (stripped.len() / 2 + stripped.len() % 2) as i32;
-					*current_memory_location += num_bytes;
-				} else {
-					println!("Error (line {}): Invalid or no operand provided for directive.", line_number);
-					exit(1);
+					return Err(Diagnostic::error(line_number, "Invalid or no operand provided for directive."));
 				}
+				*current_memory_location += decode_byte_constant(operand.unwrap(), line_number)?.len() as i32;
 			}
 			"WORD" => {
-				let word = parse_str_i32_or_error(operand, 10, format!("Error (line {}): Invalid or no operand provided for directive.", line_number));
-				if word > 8388607 || word < -8388608 {
-					println!("Error (line {}): Invalid word value provided! Outside of 24 bit limit.", line_number);
-					exit(1);
+				if operand.is_none() {
+					return Err(Diagnostic::error(line_number, "Invalid or no operand provided for directive."));
 				}
-				*current_memory_location += 3;
+				// values may be symbols defined later, so only the number of words is needed here
+				let values = split_operand_list(operand.unwrap());
+				if values.iter().any(|value| value.is_empty()) {
+					return Err(Diagnostic::error(line_number, "Empty value in WORD list!"));
+				}
+				*current_memory_location += values.len() as i32 * 3;
 			}
 			"RESB" => {
-				let num_bytes = parse_str_i32_or_error(operand, 10, format!("Error (line {}): Invalid or no operand provided for directive.", line_number));
+				let num_bytes = parse_number_or_error(operand, 10, NumericRange::Address, line_number)?;
 				*current_memory_location += num_bytes;
 			}
 			"RESW" => {
-				let num_words = parse_str_i32_or_error(operand, 10, format!("Error (line {}): Invalid or no operand provided for directive.", line_number));
+				let num_words = parse_number_or_error(operand, 10, NumericRange::Address, line_number)?;
 				*current_memory_location += num_words * 3;
 			}
+			"BASE" => {
+				if operand.is_none() {
+					return Err(Diagnostic::error(line_number, "Base directive has no symbol!"));
+				}
+			}
 			"RESR" => {
 				*current_memory_location += 3;
 			}
@@ -203,76 +376,463 @@ impl SymbolTable {
 			}
 			&_ => {}
 		}
+
+		Ok(())
 	}
 
-	fn add_symbol(&mut self, line_number: i32, name: &str, memory_location: i32) {
-		let str = String::from(name);
+	fn max_symbol_length(&self) -> usize {
+		if self.source_format.long_names { MAX_LONG_SYMBOL_LENGTH } else { MAX_SYMBOL_LENGTH }
+	}
 
-		let first_char = str.chars().next().unwrap();
+	/// Adds a label and returns the name it is stored under: .LOCAL labels become GLOBAL.LOCAL
+	/// for the last global label, and numeric nH labels become nH.k for their k-th definition.
+	fn add_symbol(&mut self, line_number: usize, name: &str, memory_location: i32) -> AsmResult<String> {
+		let qualified_name = if let Some(local_name) = name.strip_prefix(".") {
+			check_symbol_name(line_number, local_name, self.max_symbol_length())?;
+			if self.local_scope.is_empty() {
+				return Err(Diagnostic::error(line_number, format!("Local label {} has no global label before it!", name)));
+			}
+			format!("{}.{}", self.local_scope, local_name)
+		} else if let Some(digit) = numeric_label_digit(name, 'H') {
+			let count = self.numeric_labels.iter().filter(|(_, other, _)| *other == digit).count();
+			let qualified_name = format!("{}H.{}", digit, count + 1);
+			self.numeric_labels.push((line_number, digit, qualified_name.clone()));
+			qualified_name
+		} else {
+			check_symbol_name(line_number, name, self.max_symbol_length())?;
+			self.local_scope = name.to_string();
+			name.to_string()
+		};
 
-		if !first_char.is_alphabetic() || !first_char.is_uppercase() {
-			println!("Error (line {}): Symbol must start with uppercase alpha character.", line_number);
-			exit(1);
-		} else if str.len() > 6 {
-			println!("Error (line {}): Symbol greater than max length (6)", line_number);
-			exit(1);
-		} else if str.contains("$") || str.contains("!") || str.contains("=")
-			|| str.contains("+") || str.contains("-") || str.contains("(")
-			|| str.contains(")") || str.contains("@") {
-			println!("Error (line {}): Symbol contains illegal characer", line_number);
-			exit(1);
-		} else if is_directive(str.as_str()) {
-			println!("Error (line {}): Symbol cannot be a directive name!", line_number);
-			exit(1);
-		} else if self.contains_symbol(str.as_str()) {
-			println!("Error (line {}): Symbol already exists!", line_number);
-			exit(1);
+		if self.contains_symbol(&qualified_name) {
+			return Err(Diagnostic::error(line_number, "Symbol already exists!"));
 		}
 
-		for c in str.chars() {
-			if c.is_alphabetic() && !c.is_uppercase() {
-				println!("Error (line {}): Symbol cannot contain lowercase letters!", line_number);
-				exit(1);
+		self.insert_symbol(Symbol {
+			name: qualified_name.clone(),
+			memory_location,
+			relative: true,
+			line_number,
+			length: 0,
+			block: None,
+			control_section: String::new(),
+			external: false,
+		});
+
+		Ok(qualified_name)
+	}
+
+	/// Rewrites local label references in operands to qualified names: .LOCAL for the global
+	/// label in effect on the line, nB for the last nH before the line and nF for the next one.
+	fn resolve_local_references(&mut self) -> AsmResult<()> {
+		let mut scope = String::new();
+		let mut error: Option<Diagnostic> = None;
+
+		for index in 0..self.lines.len() {
+			let line_number = index + 1;
+			if let Some(label) = &self.lines[index].label {
+				if !label.contains('.') {
+					scope = label.clone();
+				}
+			}
+
+			// START takes a hex operand, where 1F is a number and not a label
+			if self.lines[index].operation.as_deref() == Some("START") {
+				continue;
+			}
+			let operand = match &self.lines[index].operand {
+				Some(operand) => operand.clone(),
+				None => continue,
+			};
+
+			let resolved = map_operand_symbols(&operand, |name| {
+				if let Some(local_name) = name.strip_prefix(".") {
+					if scope.is_empty() {
+						error.get_or_insert(Diagnostic::error(line_number, format!("Local label {} has no global label before it!", name)));
+					}
+					return format!("{}.{}", scope, local_name);
+				}
+
+				if let Some(digit) = numeric_label_digit(name, 'B') {
+					return match self.numeric_labels.iter().rev().find(|(line, other, _)| *other == digit && *line < line_number) {
+						Some((_, _, qualified_name)) => qualified_name.clone(),
+						None => {
+							error.get_or_insert(Diagnostic::error(line_number, format!("No {}H label before {}!", digit, name)));
+							name.to_string()
+						}
+					};
+				}
+
+				if let Some(digit) = numeric_label_digit(name, 'F') {
+					return match self.numeric_labels.iter().find(|(line, other, _)| *other == digit && *line > line_number) {
+						Some((_, _, qualified_name)) => qualified_name.clone(),
+						None => {
+							error.get_or_insert(Diagnostic::error(line_number, format!("No {}H label after {}!", digit, name)));
+							name.to_string()
+						}
+					};
+				}
+
+				name.to_string()
+			});
+			if let Some(error) = error {
+				return Err(error);
 			}
+			self.lines[index].operand = Some(resolved);
 		}
 
-		let symbol = Symbol {
-			name: str,
-			memory_location,
-		};
-		self.symbols.push(symbol);
+		Ok(())
 	}
 }
 
-pub fn sic_line_to_vector(line: String) -> Vec<String> {
-	let mut temp: String = String::new();
-	let mut vector: Vec<String> = vec![];
+/// Checks a label against the SIC symbol rules.
+fn check_symbol_name(line_number: usize, name: &str, max_length: usize) -> AsmResult<()> {
+	let str = String::from(name);
+
+	let first_char = str.chars().next().unwrap_or_default();
+
+	if !first_char.is_alphabetic() || !first_char.is_uppercase() {
+		return Err(Diagnostic::error(line_number, "Symbol must start with uppercase alpha character."));
+	} else if str.len() > max_length {
+		return Err(Diagnostic::error(line_number, format!("Symbol greater than max length ({})", max_length)));
+	} else if str.contains("$") || str.contains("!") || str.contains("=")
+		|| str.contains("+") || str.contains("-") || str.contains("(")
+		|| str.contains(")") || str.contains("@") || str.contains(".") {
+		return Err(Diagnostic::error(line_number, "Symbol contains illegal characer"));
+	} else if is_directive(str.as_str()) {
+		return Err(Diagnostic::error(line_number, "Symbol cannot be a directive name!"));
+	}
+
+	for c in str.chars() {
+		if c.is_alphabetic() && !c.is_uppercase() {
+			return Err(Diagnostic::error(line_number, "Symbol cannot contain lowercase letters!"));
+		}
+	}
+
+	Ok(())
+}
+
+/// The name used for a symbol in H, D, R and M records, which only have room for 6 characters.
+/// Longer names are truncated, so the mapping only depends on the name itself. The program name is
+/// the only name written to them, so two names can't collide until D and R records exist.
+pub fn object_record_name(name: &str) -> String {
+	name.chars().take(MAX_SYMBOL_LENGTH).collect()
+}
+
+/// The digit of a numeric local label such as 1H, or a 1B/1F reference, with the given suffix.
+fn numeric_label_digit(name: &str, suffix: char) -> Option<char> {
+	let mut characters = name.chars();
+	match (characters.next(), characters.next(), characters.next()) {
+		(Some(digit), Some(other), None) if digit.is_ascii_digit() && other == suffix => Some(digit),
+		_ => None,
+	}
+}
+
+/// Every name in an operand (symbols, local labels and numbers) with the character offset it
+/// starts at. Quoted constants and their C/X prefixes are skipped.
+pub fn operand_names(operand: &str) -> Vec<(usize, String)> {
+	let mut names = vec![];
+	let mut name: Option<(usize, String)> = None;
+	let mut in_string = false;
+
+	for (offset, c) in operand.chars().enumerate() {
+		if !in_string && (c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+			name.get_or_insert((offset, String::new())).1.push(c);
+			continue;
+		}
+		if c == '\'' {
+			in_string = !in_string;
+		}
+
+		// a name directly followed by a quote is a constant prefix like C' or X'
+		if c != '\'' {
+			names.extend(name.take());
+		}
+		name = None;
+	}
+	names.extend(name);
+
+	names
+}
+
+/// Calls `map` on every name in an operand (symbols, local labels and numbers) and puts
+/// what it returns in its place. Quoted constants are left alone.
+pub fn map_operand_symbols(operand: &str, mut map: impl FnMut(&str) -> String) -> String {
+	let characters: Vec<char> = operand.chars().collect();
+	let mut mapped = String::new();
+	let mut copied = 0;
+
+	for (offset, name) in operand_names(operand) {
+		mapped.extend(&characters[copied..offset]);
+		mapped.push_str(&map(&name));
+		copied = offset + name.chars().count();
+	}
+	mapped.extend(&characters[copied..]);
+
+	mapped
+}
+
+/// Symbol names referenced by an operand.
+pub fn operand_symbols(operand: &str) -> Vec<String> {
+	let mut names = vec![];
+	map_operand_symbols(operand, |name| {
+		names.push(name.to_string());
+		name.to_string()
+	});
+	names
+}
+
+/// '.' comment lines, except for a .LOCAL: label definition in column 1.
+pub fn is_dot_comment(line: &str) -> bool {
+	line.trim_start().starts_with(".") && local_label_definition(line).is_none()
+}
+
+/// The name of a local label defined as .NAME: in column 1. The colon is what tells it apart
+/// from a comment, so a comment such as .READ AND WRITE is never read as a label.
+pub fn local_label_definition(line: &str) -> Option<&str> {
+	let field = line.split_whitespace().next()?;
+	let name = line.strip_prefix(field).and(field.strip_prefix("."))?.strip_suffix(":")?;
+	name.starts_with(|c: char| c.is_ascii_uppercase()).then_some(name)
+}
+
+/// BASE and NOBASE take effect from the line after the directive, so every line
+/// records the base operand that was in effect when it was reached.
+pub fn track_base_directives(lines: &mut [SourceLine]) {
+	let mut base: Option<String> = None;
+
+	for line in lines {
+		line.base = base.clone();
+
+		match line.operation.as_deref() {
+			Some("BASE") => base = line.operand.clone(),
+			Some("NOBASE") => base = None,
+			_ => {}
+		}
+	}
+}
+
+/// Splits a line into label, operation and operand. Anything in column 1 is a label and
+/// leading whitespace means there is none. A line holding only a label has no operation, and
+/// anything after the operand (or after an operation that takes none) is a comment.
+fn split_fields(line: &str, split: &[String], line_number: usize) -> AsmResult<(Option<String>, Option<String>, Option<String>)> {
+	let (label, rest) = if line.starts_with(char::is_whitespace) {
+		(None, split)
+	} else {
+		(Some(split[0].clone()), &split[1..])
+	};
+
+	let operation = match rest.first() {
+		Some(operation) if is_instruction(operation) || is_directive(operation) => operation.clone(),
+		None if !label.as_ref().is_some_and(|label| is_instruction(label) || is_directive(label)) => {
+			return Ok((label, None, None));
+		}
+		_ => {
+			return Err(match label.as_ref().filter(|label| is_instruction(label) || is_directive(label)) {
+				Some(label) => Diagnostic::error(line_number, format!("{} in column 1 is read as a label! Indent the line if {} is the operation.",
+				                                                      label, label)),
+				None => Diagnostic::error(line_number, "Invalid line! Not an instruction or directive!"),
+			});
+		}
+	};
+
+	let operand = if takes_operand(&operation) { rest.get(1).cloned() } else { None };
+
+	Ok((label, Some(operation), operand))
+}
+
+/// The original field rules kept for --strict: one field is an operation, two fields are
+/// operation and operand or label and operation, three or more are label, operation and operand.
+fn split_fields_strict(split: &[String], line_number: usize) -> AsmResult<(Option<String>, Option<String>, Option<String>)> {
+	let str1 = split.first().unwrap();
+
+	if split.len() == 1 {
+		if !is_instruction(str1) && !is_directive(str1) {
+			return Err(Diagnostic::error(line_number, "Not an instruction!"));
+		}
+		return Ok((None, Some(str1.clone()), None));
+	}
+
+	let str2 = split.get(1).unwrap();
+
+	if split.len() == 2 {
+		if is_instruction(str1) {
+			Ok((None, Some(str1.clone()), Some(str2.clone())))
+		} else if is_instruction(str2) {
+			Ok((Some(str1.clone()), Some(str2.clone()), None))
+		} else if is_directive(str1) {
+			Ok((None, Some(str1.clone()), Some(str2.clone())))
+		} else if is_directive(str2) {
+			Ok((Some(str1.clone()), Some(str2.clone()), None))
+		} else {
+			Err(Diagnostic::error(line_number, "Invalid line! Not an instruction or directive!"))
+		}
+	} else {
+		if !is_instruction(str2) && !is_directive(str2) {
+			return Err(Diagnostic::error(line_number, "Invalid line! Not an instruction or directive!"));
+		}
+		Ok((Some(str1.clone()), Some(str2.clone()), Some(split.get(2).unwrap().clone())))
+	}
+}
+
+/// Splits a fixed column line: label in columns 1-8, opcode in 10-15 and operand in 17-35 with
+/// comments after that. A format 4 '+' may go in column 9 and an addressing prefix in column 16.
+fn split_fixed_columns(line: &str, line_number: usize) -> AsmResult<(Option<String>, Option<String>, Option<String>)> {
+	let characters: Vec<char> = line.chars().collect();
+	let column = |number: usize| characters.get(number - 1).copied().unwrap_or(' ');
+	let field = |first: usize, last: usize| -> String {
+		characters.iter().skip(first - 1).take(last + 1 - first).collect::<String>().trim().to_string()
+	};
+
+	if !column(9).is_whitespace() && column(9) != '+' {
+		return Err(Diagnostic::error(line_number, "Label overflows columns 1-8!"));
+	}
+	if !column(16).is_whitespace() && !['#', '@', '='].contains(&column(16)) {
+		return Err(Diagnostic::error(line_number, "Opcode overflows columns 10-15!"));
+	}
+	let label = field(1, 8);
+	if label.contains(char::is_whitespace) {
+		return Err(Diagnostic::error(line_number, format!("Label {} in columns 1-8 contains spaces!", label)));
+	}
+
+	let label = Some(label).filter(|label| !label.is_empty());
+
+	let operation = field(9, 15);
+	if operation.is_empty() && field(16, 35).is_empty() {
+		return Ok((label, None, None));
+	} else if !is_instruction(&operation) && !is_directive(&operation) {
+		return Err(Diagnostic::error(line_number, "Invalid line! Not an instruction or directive in columns 10-15!"));
+	}
+
+	if !takes_operand(&operation) {
+		return Ok((label, Some(operation), None));
+	}
+
+	// the operand ends at the first space outside of quotes, anything after it is a comment
+	let rest: String = characters.iter().skip(15).collect();
+	let operand = sic_line_to_vector(rest.clone(), true).into_iter().next();
+	if let Some(operand) = &operand {
+		let last_column = 15 + rest.len() - rest.trim_start().len() + operand.chars().count();
+		if last_column > 35 {
+			return Err(Diagnostic::error(line_number, format!("Operand {} overflows columns 17-35!", operand)));
+		}
+	}
+
+	Ok((label, Some(operation), operand))
+}
+
+/// Guesses whether a source file is written in fixed columns: no tabs, every line splits into
+/// fixed column fields without overflowing them, and at least one has a known opcode in columns 10-15.
+/// Anything else is read as free-form, so overflow errors only come from an explicit --fixed-columns.
+pub fn detect_fixed_columns(lines: &[String]) -> bool {
+	let mut statements = 0;
+
+	for line in lines {
+		if line.starts_with("#") || line.trim_start().starts_with(".") || line.trim().is_empty() {
+			continue;
+		}
+		if line.contains('\t') {
+			return false;
+		}
+
+		match split_fixed_columns(line, 0) {
+			Ok((_, Some(_), _)) => statements += 1,
+			// a line holding only a label fits either layout
+			Ok((_, None, _)) => {}
+			Err(_) => return false,
+		}
+	}
+
+	statements > 0
+}
+
+/// Splits a line on whitespace outside of quotes. Unless `strict`, a field starting with '.'
+/// begins a trailing comment and ends the line.
+pub fn sic_line_to_vector(line: String, strict: bool) -> Vec<String> {
+	sic_line_fields(&line, strict).into_iter().map(|(_, field)| field).collect()
+}
+
+/// The fields of `sic_line_to_vector` with the character column each starts at.
+pub fn sic_line_fields(line: &str, strict: bool) -> Vec<(usize, String)> {
+	let mut fields: Vec<(usize, String)> = vec![];
+	let mut current: Option<(usize, String)> = None;
 
 	let mut in_string: bool = false;
 
-	for c in line.chars() {
+	let mut characters = line.chars().enumerate().peekable();
+	while let Some((column, c)) = characters.next() {
 		if c == '\r' || c == '\n' {
-			if temp.len() > 0 {
-				vector.push(temp);
-				temp = String::new();
+			fields.extend(current.take());
+		} else if c == '.' && !strict && !in_string && current.is_none() && !fields.is_empty() {
+			// .LOCAL label references start with an uppercase letter, comments don't
+			if !characters.peek().is_some_and(|(_, next)| next.is_ascii_uppercase()) {
+				break;
 			}
+			current = Some((column, c.to_string()));
 		} else if c == ' ' || c == '\t' {
-			if !in_string && temp.len() > 0 {
-				vector.push(temp);
-				temp = String::new();
-			} else if in_string {
-				temp.push(c);
+			if !in_string {
+				fields.extend(current.take());
+			} else if let Some((_, field)) = &mut current {
+				field.push(c);
 			}
 		} else {
 			if c == '\'' {
 				in_string = !in_string;
 			}
-			temp.push(c);
+			current.get_or_insert((column, String::new())).1.push(c);
+		}
+	}
+	fields.extend(current);
+
+	fields
+}
+
+/// The label, operation and operand of a line as written, with the character column each starts
+/// at, split the way pass 1 splits it. Fields the line doesn't have are left out, so the first
+/// field of a line without a label is its operation.
+pub fn source_fields(line: &str, source_format: &SourceFormat) -> Vec<(usize, String)> {
+	if !source_format.fixed_columns {
+		return sic_line_fields(line, source_format.strict).into_iter().take(3).collect();
+	}
+
+	// label in columns 1-8, operation (with a '+' in column 9) in 9-15 and operand from 16 on
+	let characters: Vec<char> = line.chars().collect();
+	let mut fields = vec![];
+	for (first, last) in [(0, 8), (8, 15)] {
+		let field: String = characters.iter().take(last).skip(first).collect();
+		if !field.trim().is_empty() {
+			fields.push((first + field.len() - field.trim_start().len(), field.trim().to_string()));
 		}
 	}
-	if temp.len() > 0 {
-		vector.push(temp);
+	let rest: String = characters.iter().skip(15).collect();
+	fields.extend(sic_line_fields(&rest, true).into_iter().take(1).map(|(column, operand)| (column + 15, operand)));
+
+	fields
+}
+
+// splits an expression into signed terms, leaving quoted constants such as C'+' intact
+fn split_expression_terms(expression: &str) -> Vec<(i32, String)> {
+	let mut terms: Vec<(i32, String)> = vec![];
+	let mut sign = 1;
+	let mut temp = String::new();
+	let mut in_string = false;
+
+	for (index, c) in expression.chars().enumerate() {
+		if c == '\'' {
+			in_string = !in_string;
+		}
+
+		if (c == '+' || c == '-') && !in_string {
+			// a leading sign belongs to the first term, any other starts a new one
+			if index > 0 {
+				terms.push((sign, temp));
+				temp = String::new();
+			}
+			sign = if c == '+' { 1 } else { -1 };
+		} else {
+			temp.push(c);
+		}
 	}
+	terms.push((sign, temp));
 
-	vector
-}
\ No newline at end of file
+	terms
+}
@@ -1,52 +1,231 @@
+use std::fmt;
 use std::fs::File;
 use std::fs;
 use std::io;
-use std::io::BufRead;
+use std::io::{BufRead, Write};
 use std::path::Path;
-use std::process::exit;
 
 pub fn read_lines<P: AsRef<Path>>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>> {
-	let file_result = File::open(filename);
-	if file_result.is_err() {
-		println!("Could not open file!");
-		exit(1);
+	let file = File::open(filename)?;
+	Ok(io::BufReader::new(file).lines())
+}
+
+/// Reads a whole source file into memory. A filename of "-" reads from stdin.
+pub fn read_source(filename: &str) -> io::Result<Vec<String>> {
+	if filename == "-" {
+		io::stdin().lock().lines().collect()
 	} else {
-		let file = file_result.unwrap();
-		let reader = io::BufReader::new(file);
-		return Ok(reader.lines());
+		io::BufReader::new(File::open(filename)?).lines().collect()
+	}
+}
+
+pub fn write_lines(filename: &str, lines: Vec<String>) -> io::Result<()> {
+	let mut joined = lines.join("\n");
+	if filename == "-" {
+		joined.push('\n');
 	}
+	write_bytes(filename, joined.as_bytes())
 }
 
-pub fn write_lines<P: AsRef<Path>>(filename: P, lines: Vec<String>) {
-	let joined = lines.join("\n");
-	let result = fs::write(filename, joined);
-	if result.is_err() {
-		println!("Could not write to file! Check folder permissions.");
+/// Writes output to a file. A filename of "-" writes to stdout.
+pub fn write_bytes(filename: &str, bytes: &[u8]) -> io::Result<()> {
+	if filename == "-" {
+		let mut stdout = io::stdout().lock();
+		stdout.write_all(bytes)?;
+		stdout.flush()
+	} else {
+		fs::write(filename, bytes)
 	}
 }
 
-pub fn parse_str_i32_or_error(str: Option<&str>, base: u32, error_message: String) -> i32 {
-	if str.is_none() {
-		println!("{}", error_message);
-		exit(1);
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+/// An error or warning about a source line. Line 0 means the whole file.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub line_number: usize,
+	pub message: String,
+}
+
+impl Diagnostic {
+	pub fn error(line_number: usize, message: impl Into<String>) -> Diagnostic {
+		Diagnostic { severity: Severity::Error, line_number, message: message.into() }
 	}
-	let parsed = i32::from_str_radix(str.unwrap(), base);
-	if parsed.is_err() {
-		println!("{}", error_message);
-		exit(1);
+
+	pub fn warning(line_number: usize, message: impl Into<String>) -> Diagnostic {
+		Diagnostic { severity: Severity::Warning, line_number, message: message.into() }
 	}
-	return parsed.unwrap();
 }
 
-pub fn parse_string_i32_or_error(str: Option<&String>, base: u32, error_message: String) -> i32 {
-	if str.is_none() {
-		println!("{}", error_message);
-		exit(1);
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let severity = match self.severity {
+			Severity::Error => "Error",
+			Severity::Warning => "Warning",
+		};
+		if self.line_number == 0 {
+			write!(f, "{}: {}", severity, self.message)
+		} else {
+			write!(f, "{} (line {}): {}", severity, self.line_number, self.message)
+		}
 	}
-	let parsed = i32::from_str_radix(str.unwrap(), base);
-	if parsed.is_err() {
-		println!("{}", error_message);
-		exit(1);
+}
+
+/// Assembling stops at the first error in the source.
+pub type AsmResult<T> = Result<T, Diagnostic>;
+
+pub fn parse_str_i32_or_error(str: Option<&str>, base: u32, error: Diagnostic) -> AsmResult<i32> {
+	match str.map(|str| i32::from_str_radix(str, base)) {
+		Some(Ok(value)) => Ok(value),
+		_ => Err(error),
 	}
-	return parsed.unwrap();
+}
+
+/// Where a numeric operand is used, which decides the range it has to fit in.
+#[derive(Clone, Copy)]
+pub enum NumericRange {
+	/// 12 bit format 3 displacement or immediate
+	Displacement,
+	/// 20 bit SIC/XE address, also used for START and reservation sizes
+	Address,
+	/// 15 bit address of a SIC instruction
+	SicAddress,
+	/// 24 bit word, signed or unsigned
+	Word,
+	/// single byte of a BYTE constant, signed or unsigned
+	Byte,
+}
+
+impl NumericRange {
+	pub fn bounds(&self) -> (i32, i32, &str) {
+		match self {
+			NumericRange::Displacement => (0, 4095, "12 bit displacement"),
+			NumericRange::Address => (0, 1048575, "20 bit address"),
+			NumericRange::SicAddress => (0, 32767, "15 bit SIC address"),
+			NumericRange::Word => (-8388608, 16777215, "24 bit word"),
+			NumericRange::Byte => (-128, 255, "byte"),
+		}
+	}
+}
+
+/// Parses a numeric literal: decimal, X'1F' or 0x1F hex, B'0101' binary, O'17' octal or C'A' characters.
+/// Plain numbers are read in `default_radix`, which is 16 for START and 10 everywhere else.
+pub fn parse_numeric_literal(str: &str, default_radix: u32) -> Option<i32> {
+	if let Some(characters) = str.strip_prefix("C'").and_then(|s| s.strip_suffix("'")) {
+		if characters.is_empty() || characters.len() > 3 || !characters.is_ascii() {
+			return None;
+		}
+		return Some(characters.bytes().fold(0, |value, byte| (value << 8) + byte as i32));
+	}
+
+	let (digits, radix) = if let Some(digits) = str.strip_prefix("X'").and_then(|s| s.strip_suffix("'")) {
+		(digits, 16)
+	} else if let Some(digits) = str.strip_prefix("B'").and_then(|s| s.strip_suffix("'")) {
+		(digits, 2)
+	} else if let Some(digits) = str.strip_prefix("O'").and_then(|s| s.strip_suffix("'")) {
+		(digits, 8)
+	} else if let Some(digits) = str.strip_prefix("0x").or_else(|| str.strip_prefix("0X")) {
+		(digits, 16)
+	} else {
+		let (negative, digits) = match str.strip_prefix("-") {
+			Some(digits) => (true, digits),
+			None => (false, str),
+		};
+		let value = parse_digits(digits, default_radix)?;
+		return Some(if negative { -value } else { value });
+	};
+
+	parse_digits(digits, radix)
+}
+
+fn parse_digits(digits: &str, radix: u32) -> Option<i32> {
+	if digits.is_empty() || !digits.chars().all(|c| c.is_digit(radix)) {
+		return None;
+	}
+	i32::from_str_radix(digits, radix).ok()
+}
+
+pub fn parse_number_or_error(str: Option<&str>, default_radix: u32, range: NumericRange, line_number: usize) -> AsmResult<i32> {
+	let str = match str {
+		Some(str) => str,
+		None => {
+			return Err(Diagnostic::error(line_number, "Missing numeric operand!"));
+		}
+	};
+
+	let value = match parse_numeric_literal(str, default_radix) {
+		Some(value) => value,
+		None => {
+			return Err(Diagnostic::error(line_number, format!("Invalid numeric operand '{}'!", str)));
+		}
+	};
+
+	let (min, max, description) = range.bounds();
+	if value < min || value > max {
+		return Err(Diagnostic::error(line_number, format!("{} is out of range for a {} ({} to {})!", str, description, min, max)));
+	}
+
+	Ok(value)
+}
+
+/// Splits a comma separated operand list, ignoring commas inside quoted constants like C','.
+pub fn split_operand_list(operand: &str) -> Vec<String> {
+	let mut values: Vec<String> = vec![];
+	let mut temp = String::new();
+	let mut in_string = false;
+
+	for c in operand.chars() {
+		if c == '\'' {
+			in_string = !in_string;
+		}
+
+		if c == ',' && !in_string {
+			values.push(temp);
+			temp = String::new();
+		} else {
+			temp.push(c);
+		}
+	}
+	values.push(temp);
+
+	values
+}
+
+/// Decodes a BYTE operand, a comma separated list of C'..' characters (with '' for a quote),
+/// X'..' hex digits and single byte numbers. Used by both passes so they agree on the size.
+pub fn decode_byte_constant(operand: &str, line_number: usize) -> AsmResult<Vec<u8>> {
+	let mut bytes: Vec<u8> = vec![];
+
+	for value in split_operand_list(operand) {
+		if let Some(characters) = value.strip_prefix("C'").and_then(|s| s.strip_suffix("'")) {
+			if !characters.is_ascii() {
+				return Err(Diagnostic::error(line_number, format!("{} contains non-ASCII characters, only ASCII is allowed in C'..' constants!", value)));
+			}
+			if characters.replace("''", "").contains('\'') {
+				return Err(Diagnostic::error(line_number, format!("Unescaped quote in {}, use '' for a quote!", value)));
+			}
+			if characters.is_empty() {
+				return Err(Diagnostic::error(line_number, "Empty character constant!"));
+			}
+			bytes.extend(characters.replace("''", "'").bytes());
+		} else if let Some(digits) = value.strip_prefix("X'").and_then(|s| s.strip_suffix("'")) {
+			if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+				return Err(Diagnostic::error(line_number, format!("Invalid hex constant {}!", value)));
+			}
+			if !digits.len().is_multiple_of(2) {
+				return Err(Diagnostic::error(line_number, format!("Hex constant {} has an odd number of digits!", value)));
+			}
+			bytes.extend(hex::decode(digits).unwrap());
+		} else {
+			let byte = parse_number_or_error(Some(&value), 10, NumericRange::Byte, line_number)?;
+			bytes.push(byte as u8);
+		}
+	}
+
+	Ok(bytes)
 }
@@ -0,0 +1,256 @@
+use crate::util::*;
+
+struct TextRange {
+	line_number: usize,
+	start: i32,
+	end: i32,
+}
+
+struct ModificationCheck {
+	line_number: usize,
+	start: i32,
+	half_bytes: i32,
+}
+
+pub fn verify_object_file(filename: &str) -> Vec<String> {
+	let mut problems: Vec<String> = vec![];
+
+	let mut header: Option<(i32, i32)> = None;
+	let mut end_address: Option<(usize, i32)> = None;
+	let mut end_seen = false;
+	let mut text_ranges: Vec<TextRange> = vec![];
+	let mut modifications: Vec<ModificationCheck> = vec![];
+
+	let lines = match read_lines(filename) {
+		Ok(lines) => lines,
+		Err(error) => return vec![format!("Error: Could not open {}: {}!", filename, error)],
+	};
+
+	for (line_number, record) in lines.enumerate() {
+		let line_number = line_number + 1;
+		let record = match record {
+			Ok(record) => record,
+			Err(_) => {
+				problems.push(format!("Error (line {}): Could not read record!", line_number));
+				break;
+			}
+		};
+
+		if end_seen {
+			problems.push(format!("Error (line {}): Record found after end record!", line_number));
+			continue;
+		}
+
+		match record.chars().next() {
+			None => {
+				problems.push(format!("Error (line {}): Empty record!", line_number));
+			}
+			Some('H') => {
+				if line_number != 1 {
+					problems.push(format!("Error (line {}): Header record must be the first record!", line_number));
+				}
+				if let Some(parsed) = verify_header_record(&record, line_number, &mut problems) {
+					header = Some(parsed);
+				}
+			}
+			Some('T') => {
+				if let Some(range) = verify_text_record(&record, line_number, &mut problems) {
+					text_ranges.push(range);
+				}
+			}
+			Some('M') => {
+				if let Some(modification) = verify_modification_record(&record, line_number, &mut problems) {
+					modifications.push(modification);
+				}
+			}
+			Some('D') => {
+				verify_define_record(&record, line_number, &mut problems);
+			}
+			Some('R') => {
+				verify_refer_record(&record, line_number, &mut problems);
+			}
+			Some('E') => {
+				end_seen = true;
+				let body = &record[1..];
+				if body.is_empty() {
+					continue;
+				}
+				match parse_hex_field(body) {
+					Some(address) if body.len() == 6 => end_address = Some((line_number, address)),
+					_ => problems.push(format!("Error (line {}): End record address must be 6 hex digits!", line_number)),
+				}
+			}
+			Some(other) => {
+				problems.push(format!("Error (line {}): Unknown record type '{}'!", line_number, other));
+			}
+		}
+	}
+
+	if header.is_none() {
+		problems.push("Error: No valid header record found!".to_string());
+	}
+	if !end_seen {
+		problems.push("Error: No end record found!".to_string());
+	}
+
+	text_ranges.sort_by_key(|range| range.start);
+
+	if let Some((program_start, program_length)) = header {
+		let program_end = program_start + program_length;
+
+		for range in &text_ranges {
+			if range.start < program_start || range.end > program_end {
+				problems.push(format!("Error (line {}): Text record {:0>6X}-{:0>6X} is outside of program range {:0>6X}-{:0>6X}!",
+				                      range.line_number, range.start, range.end, program_start, program_end));
+			}
+		}
+
+		if let Some((line_number, address)) = end_address {
+			if address < program_start || address >= program_end {
+				problems.push(format!("Error (line {}): End address {:0>6X} is outside of program range {:0>6X}-{:0>6X}!",
+				                      line_number, address, program_start, program_end));
+			}
+		}
+	}
+
+	for pair in text_ranges.windows(2) {
+		if pair[1].start < pair[0].end {
+			problems.push(format!("Error (line {}): Text record overlaps text record on line {}!",
+			                      pair[1].line_number, pair[0].line_number));
+		}
+	}
+
+	for modification in &modifications {
+		let num_bytes = (modification.half_bytes + 1) / 2;
+		for address in modification.start..modification.start + num_bytes {
+			let covered = text_ranges.iter().any(|range| address >= range.start && address < range.end);
+			if !covered {
+				problems.push(format!("Error (line {}): Modification record points at {:0>6X}, which is not inside any text record!",
+				                      modification.line_number, address));
+				break;
+			}
+		}
+	}
+
+	problems
+}
+
+fn verify_header_record(record: &str, line_number: usize, problems: &mut Vec<String>) -> Option<(i32, i32)> {
+	// the name field is usually 6 characters, but only the trailing address fields are fixed
+	if record.len() < 13 || !record.is_ascii() {
+		problems.push(format!("Error (line {}): Header record is too short!", line_number));
+		return None;
+	}
+
+	let fields = &record[record.len() - 12..];
+	let start = parse_hex_field(&fields[..6]);
+	let length = parse_hex_field(&fields[6..]);
+
+	match (start, length) {
+		(Some(start), Some(length)) => Some((start, length)),
+		_ => {
+			problems.push(format!("Error (line {}): Header record has invalid start address or length!", line_number));
+			None
+		}
+	}
+}
+
+fn verify_text_record(record: &str, line_number: usize, problems: &mut Vec<String>) -> Option<TextRange> {
+	if record.len() < 9 || !record.is_ascii() {
+		problems.push(format!("Error (line {}): Text record is too short!", line_number));
+		return None;
+	}
+
+	let start = parse_hex_field(&record[1..7]);
+	let declared_bytes = parse_hex_field(&record[7..9]);
+	let code = &record[9..];
+
+	let (start, declared_bytes) = match (start, declared_bytes) {
+		(Some(start), Some(declared_bytes)) => (start, declared_bytes),
+		_ => {
+			problems.push(format!("Error (line {}): Text record has invalid address or length!", line_number));
+			return None;
+		}
+	};
+
+	if !code.chars().all(|c| c.is_ascii_hexdigit()) {
+		problems.push(format!("Error (line {}): Text record contains invalid hex!", line_number));
+	}
+	if !code.len().is_multiple_of(2) {
+		problems.push(format!("Error (line {}): Text record has an odd number of hex digits!", line_number));
+	}
+	if code.len() as i32 != declared_bytes * 2 {
+		problems.push(format!("Error (line {}): Text record declares {} bytes but contains {} hex digits!",
+		                      line_number, declared_bytes, code.len()));
+	}
+	if declared_bytes > 30 {
+		problems.push(format!("Error (line {}): Text record is longer than 30 bytes!", line_number));
+	}
+
+	Some(TextRange {
+		line_number,
+		start,
+		end: start + declared_bytes,
+	})
+}
+
+fn verify_modification_record(record: &str, line_number: usize, problems: &mut Vec<String>) -> Option<ModificationCheck> {
+	if record.len() < 9 || record.len() > 16 || !record.is_ascii() {
+		problems.push(format!("Error (line {}): Modification record has invalid length!", line_number));
+		return None;
+	}
+
+	let start = parse_hex_field(&record[1..7]);
+	let half_bytes = parse_hex_field(&record[7..9]);
+
+	let (start, half_bytes) = match (start, half_bytes) {
+		(Some(start), Some(half_bytes)) => (start, half_bytes),
+		_ => {
+			problems.push(format!("Error (line {}): Modification record has invalid address or length!", line_number));
+			return None;
+		}
+	};
+
+	if !(1..=6).contains(&half_bytes) {
+		problems.push(format!("Error (line {}): Modification record length must be between 1 and 6 half-bytes!", line_number));
+		return None;
+	}
+
+	let symbol = &record[9..];
+	if !symbol.is_empty() && !symbol.starts_with('+') && !symbol.starts_with('-') {
+		problems.push(format!("Error (line {}): Modification record symbol must be preceded by '+' or '-'!", line_number));
+	}
+
+	Some(ModificationCheck {
+		line_number,
+		start,
+		half_bytes,
+	})
+}
+
+fn verify_define_record(record: &str, line_number: usize, problems: &mut Vec<String>) {
+	let body = &record[1..];
+	if body.is_empty() || !body.len().is_multiple_of(12) || !body.is_ascii() {
+		problems.push(format!("Error (line {}): Define record must contain 12 character name/address pairs!", line_number));
+		return;
+	}
+
+	for index in (0..body.len()).step_by(12) {
+		if parse_hex_field(&body[index + 6..index + 12]).is_none() {
+			problems.push(format!("Error (line {}): Define record has invalid address!", line_number));
+		}
+	}
+}
+
+fn verify_refer_record(record: &str, line_number: usize, problems: &mut Vec<String>) {
+	if record.len() < 2 {
+		problems.push(format!("Error (line {}): Refer record has no symbols!", line_number));
+	}
+}
+
+fn parse_hex_field(field: &str) -> Option<i32> {
+	if field.is_empty() || !field.chars().all(|c| c.is_ascii_hexdigit()) {
+		return None;
+	}
+	i32::from_str_radix(field, 16).ok()
+}
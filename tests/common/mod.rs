@@ -0,0 +1,50 @@
+//! Runs the assembler binary for the integration tests. Not every test uses every helper.
+#![allow(dead_code)]
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+/// Runs the assembler in `directory` with `input` piped to its stdin.
+pub fn run_in(directory: &Path, args: &[&str], input: &str) -> Output {
+	let mut assembler = Command::new(env!("CARGO_BIN_EXE_sic_assembler_rust"))
+		.args(args)
+		.current_dir(directory)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	// the assembler may exit before reading its input, e.g. when an option is invalid
+	let _ = assembler.stdin.take().unwrap().write_all(input.as_bytes());
+	assembler.wait_with_output().unwrap()
+}
+
+/// Runs the assembler in the crate directory, where test.sicxe and tests/conformance are.
+pub fn run(args: &[&str], input: &str) -> Output {
+	run_in(Path::new(env!("CARGO_MANIFEST_DIR")), args, input)
+}
+
+/// The stdout of a successful run, or the stderr of a failed one.
+pub fn result(output: Output) -> Result<String, String> {
+	if output.status.success() {
+		Ok(String::from_utf8(output.stdout).unwrap())
+	} else {
+		Err(String::from_utf8(output.stderr).unwrap())
+	}
+}
+
+/// The stdout of a run that has to succeed.
+pub fn stdout(output: Output) -> String {
+	result(output).unwrap_or_else(|errors| panic!("{}", errors))
+}
+
+/// Assembles source piped to stdin and returns the output on stdout, or the errors.
+pub fn assemble(options: &[&str], source: &str) -> Result<String, String> {
+	result(run(&[options, &["-"]].concat(), source))
+}
+
+/// Assembles a file relative to the crate directory and returns the output, or the errors.
+pub fn assemble_file(options: &[&str], filename: &str) -> Result<String, String> {
+	result(run(&[options, &["-o", "-", filename]].concat(), ""))
+}
@@ -0,0 +1,139 @@
+//! Assembles the textbook programs from Beck's System Software and compares the output with the
+//! published object programs. Records are compared in a normalized form, so how the text is split
+//! into T records and the symbol after a modification record's address and length don't matter.
+//!
+//! The literal (Fig. 2.9), program block (Fig. 2.11) and control section (Fig. 2.15) versions of
+//! COPY need LTORG, USE, EQU, CSECT and EXTDEF/EXTREF, which the assembler doesn't support yet,
+//! so their tests are ignored until it does.
+
+mod common;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+
+use common::*;
+
+#[derive(Debug, PartialEq)]
+struct ObjectProgram {
+	name: String,
+	start: u32,
+	length: u32,
+	memory: BTreeMap<u32, u8>,
+	/// Address and length in half-bytes of every modification record
+	modifications: BTreeSet<(u32, u32)>,
+	entry_point: Option<u32>,
+}
+
+fn hex_field(record: &str, start: usize, end: usize) -> u32 {
+	u32::from_str_radix(&record[start..end], 16).unwrap_or_else(|_| panic!("bad hex field in {}", record))
+}
+
+/// Normalizes every control section of an object program, each starting at its H record.
+fn normalize(object_program: &str) -> Vec<ObjectProgram> {
+	let mut programs = Vec::new();
+
+	for record in object_program.lines() {
+		if record.starts_with('H') {
+			programs.push(ObjectProgram {
+				name: String::new(),
+				start: 0,
+				length: 0,
+				memory: BTreeMap::new(),
+				modifications: BTreeSet::new(),
+				entry_point: None,
+			});
+		}
+		let Some(program) = programs.last_mut() else {
+			panic!("record before the header: {}", record);
+		};
+
+		match record.chars().next() {
+			Some('H') => {
+				program.name = record[1..7].trim_end().to_string();
+				program.start = hex_field(record, 7, 13);
+				program.length = hex_field(record, 13, 19);
+			}
+			Some('T') => {
+				let start = hex_field(record, 1, 7);
+				let bytes = hex::decode(&record[9..]).unwrap();
+				assert_eq!(bytes.len() as u32, hex_field(record, 7, 9), "length of {}", record);
+				for (offset, byte) in bytes.into_iter().enumerate() {
+					program.memory.insert(start + offset as u32, byte);
+				}
+			}
+			Some('M') => {
+				program.modifications.insert((hex_field(record, 1, 7), hex_field(record, 7, 9)));
+			}
+			Some('E') if record.len() > 1 => program.entry_point = Some(hex_field(record, 1, 7)),
+			_ => {}
+		}
+	}
+
+	programs
+}
+
+fn assert_conforms(source: &str, options: &[&str], expected: &str) {
+	let expected = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join(expected)).unwrap();
+	assert_eq!(normalize(&assemble_file(options, source).unwrap()), normalize(&expected));
+}
+
+/// Fig. 2.1 assembled as in Fig. 2.3: SIC with direct addressing, loaded at 1000.
+#[test]
+fn sic_copy() {
+	assert_conforms("tests/conformance/copy.sic", &["--sic"], "tests/conformance/copy.obj");
+}
+
+/// Fig. 2.5 assembled as in Fig. 2.8: SIC/XE with pc-relative, base-relative, immediate and
+/// indirect addressing, relocatable with modification records for the format 4 instructions.
+#[test]
+fn sic_xe_copy() {
+	assert_conforms("test.sicxe", &[], "tests/conformance/copy_xe.obj");
+}
+
+/// Fig. 2.9 assembled as in Fig. 2.10: literals placed by LTORG and at the end of the program.
+#[test]
+#[ignore = "LTORG, literals and EQU aren't supported"]
+fn literals_copy() {
+	assert_conforms("tests/conformance/copy_literals.sic", &[], "tests/conformance/copy_literals.obj");
+}
+
+/// Fig. 2.11 assembled as in Fig. 2.13: the default, CDATA and CBLKS program blocks.
+#[test]
+#[ignore = "USE program blocks, literals and EQU aren't supported"]
+fn program_blocks_copy() {
+	assert_conforms("tests/conformance/copy_blocks.sic", &[], "tests/conformance/copy_blocks.obj");
+}
+
+/// Fig. 2.15 assembled as in Fig. 2.17: COPY, RDREC and WRREC control sections with external
+/// references.
+#[test]
+#[ignore = "CSECT, EXTDEF/EXTREF, literals and EQU aren't supported"]
+fn control_sections_copy() {
+	assert_conforms("tests/conformance/copy_sections.sic", &[], "tests/conformance/copy_sections.obj");
+}
+
+/// Not from the book: test28.obj is a snapshot of this assembler's own output, so it only catches
+/// changes in behavior, not whether the output was right in the first place.
+#[test]
+fn indexed_string_copy() {
+	assert_conforms("test28.sic", &[], "tests/conformance/test28.obj");
+}
+
+#[test]
+fn sic_rejects_sic_xe_features() {
+	let errors = assemble_file(&["--sic"], "test.sicxe").unwrap_err();
+	assert_eq!(errors.trim(), "Error (line 3): LDB is a SIC/XE instruction!");
+}
+
+/// Assembling the relocatable COPY somewhere else makes it absolute: the same program moved to
+/// 2000 without any modification records.
+#[test]
+fn origin_makes_program_absolute() {
+	let relocatable = normalize(&assemble_file(&[], "test.sicxe").unwrap()).remove(0);
+	let absolute = normalize(&assemble_file(&["--origin", "2000"], "test.sicxe").unwrap()).remove(0);
+	assert_eq!(absolute.start, 0x2000);
+	assert_eq!(absolute.entry_point, Some(0x2000));
+	assert!(absolute.modifications.is_empty());
+	assert_eq!(absolute.memory.len(), relocatable.memory.len());
+}
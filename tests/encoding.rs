@@ -0,0 +1,116 @@
+mod common;
+
+use common::*;
+
+/// Assembles unlabeled statements between a START and an END and returns the object code of each one.
+fn encode(statements: &[&str]) -> Result<Vec<String>, String> {
+	let body: String = statements.iter().map(|statement| format!("        {}\n", statement)).collect();
+	let object_program = assemble(&[], &format!("PROG    START   0\n{}        END\n", body))?;
+	Ok(object_program.lines().filter(|record| record.starts_with('T')).map(|record| record[9..].to_string()).collect())
+}
+
+fn encode_error(statement: &str) -> String {
+	encode(&[statement]).unwrap_err().trim_end().to_string()
+}
+
+#[test]
+fn format_2_operands() {
+	let code = encode(&["SHIFTL  T,4", "SVC     3", "CLEAR   X", "RMO     PC,SW"]).unwrap();
+	assert_eq!(code, vec!["A453", "B030", "B410", "AC89"]);
+}
+
+#[test]
+fn format_2_operand_errors() {
+	assert_eq!(encode_error("SHIFTL  T,0"), "Error (line 2): Shift count 0 is out of range (1 to 16)!");
+	assert_eq!(encode_error("SHIFTR  T,17"), "Error (line 2): Shift count 17 is out of range (1 to 16)!");
+	assert_eq!(encode_error("SVC     16"), "Error (line 2): SVC number 16 is out of range (0 to 15)!");
+	assert_eq!(encode_error("CLEAR   Q"), "Error (line 2): Unknown register Q! Registers are A, X, L, B, S, T, F, PC and SW.");
+	assert_eq!(encode_error("RMO     A"), "Error (line 2): RMO takes two registers (r1,r2), not A!");
+	assert_eq!(encode_error("TIXR    A,X"), "Error (line 2): TIXR takes a register, not A,X!");
+}
+
+#[test]
+fn numeric_literals() {
+	let code = encode(&["LDA     #31", "LDA     #X'1F'", "LDA     #0x1F", "LDA     #B'0101'", "LDA     #O'17'", "LDA     #C'A'",
+	                    "+LDA    #1048575", "RESB    X'3'", "LDA     #4095"]).unwrap();
+	assert_eq!(code, vec!["01001F", "01001F", "01001F", "010005", "01000F", "010041", "011FFFFF", "010FFF"]);
+}
+
+#[test]
+fn numeric_range_errors() {
+	assert_eq!(encode_error("LDA     #4096"), "Error (line 2): 4096 is out of range for a 12 bit displacement (0 to 4095)!");
+	assert_eq!(encode_error("+LDA    #1048576"), "Error (line 2): 1048576 is out of range for a 20 bit address (0 to 1048575)!");
+	assert_eq!(encode_error("LDA     #0x1000"), "Error (line 2): 0x1000 is out of range for a 12 bit displacement (0 to 4095)!");
+}
+
+/// Relocatable WORD entries get 6 half-byte modification records, differences of two labels don't.
+#[test]
+fn word_lists_and_expressions() {
+	let source = "\
+PROG    START   0
+BEGIN   WORD    1,2,-3
+        WORD    BEGIN
+        WORD    ENDP-BEGIN
+TABLE   WORD    CASE0,CASE1
+CASE0   RSUB
+CASE1   RSUB
+ENDP    WORD    30-BEGIN
+        END     BEGIN
+";
+	let object_program = assemble(&[], source).unwrap();
+	assert_eq!(object_program, "\
+HPROG  00000000001E
+T00000009000001000002FFFFFD
+T00000903000000
+T00000C0300001B
+T00000F06000015000018
+T000015034F0000
+T000018034F0000
+T00001B0300001E
+M00000906+PROG  
+M00000F06+PROG  
+M00001206+PROG  
+M00001B06-PROG  
+E000000
+");
+}
+
+#[test]
+fn word_errors() {
+	assert_eq!(encode_error("WORD    16777216"), "Error (line 2): 16777216 is out of range for a 24 bit word (-8388608 to 16777215)!");
+	assert_eq!(encode_error("WORD    -8388609"), "Error (line 2): -8388609 is out of range for a 24 bit word (-8388608 to 16777215)!");
+	assert_eq!(encode_error("WORD    1,,2"), "Error (line 2): Empty value in WORD list!");
+	assert_eq!(encode_error("WORD    NOPE"), "Error (line 2): Undefined symbol NOPE!");
+}
+
+/// Both passes size BYTE constants with the same decoder, so the label after them is where the bytes end.
+#[test]
+fn byte_lists_and_escapes() {
+	let source = "\
+PROG    START   0
+        BYTE    C'EOF',X'00',10
+        BYTE    C'IT''S'
+        BYTE    -1,C'A B'
+AFTER   WORD    AFTER
+        END
+";
+	let object_program = assemble(&[], source).unwrap();
+	assert_eq!(object_program, "\
+HPROG  000000000010
+T00000005454F46000A
+T0000050449542753
+T00000904FF412042
+T00000D0300000D
+M00000D06+PROG  
+E000000
+");
+}
+
+#[test]
+fn byte_errors() {
+	assert_eq!(encode_error("BYTE    X'G1'"), "Error (line 2): Invalid hex constant X'G1'!");
+	assert_eq!(encode_error("BYTE    X'123'"), "Error (line 2): Hex constant X'123' has an odd number of digits!");
+	assert_eq!(encode_error("BYTE    C'é'"), "Error (line 2): C'é' contains non-ASCII characters, only ASCII is allowed in C'..' constants!");
+	assert_eq!(encode_error("BYTE    256"), "Error (line 2): 256 is out of range for a byte (-128 to 255)!");
+	assert_eq!(encode_error("BYTE    C''"), "Error (line 2): Empty character constant!");
+}
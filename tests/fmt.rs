@@ -0,0 +1,31 @@
+mod common;
+
+use common::*;
+
+fn format(source: &str) -> String {
+	stdout(run(&["fmt", "-"], source))
+}
+
+const SOURCE: &str = "\
+PROG START 0
+. comments stay as they are
+FIRST LDA   #0x1f
+  LDCH BUF,X   trailing comment
+QUOTE   BYTE C'it''s ok',X'0a',010
+BUF  RESB 4
+  WORD QUOTE-FIRST,-1
+    END FIRST
+";
+
+/// Formatting never changes what a program assembles to, including quotes in C'..' constants.
+#[test]
+fn formatted_source_assembles_to_the_same_object_code() {
+	let formatted = format(SOURCE);
+	assert_ne!(formatted, SOURCE);
+	assert!(formatted.contains("C'it''s ok',X'0A',10"), "{}", formatted);
+	assert_eq!(assemble(&[], &formatted), assemble(&[], SOURCE));
+	assert!(assemble(&[], SOURCE).is_ok());
+
+	// formatting is stable
+	assert_eq!(format(&formatted), formatted);
+}
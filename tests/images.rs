@@ -0,0 +1,78 @@
+mod common;
+
+use common::*;
+
+/// Sixteen bytes, so a whole image is one 16 byte data record unless it is split.
+const DATA: &str = "\
+PROG    START   0
+DATA    BYTE    X'00112233445566778899AABBCCDDEEFF'
+        END     DATA
+";
+
+fn record_bytes(record: &str) -> Vec<u8> {
+	hex::decode(record).unwrap_or_else(|error| panic!("{}: {}", record, error))
+}
+
+/// The bytes of every Intel HEX record, checksum included, add up to zero.
+fn assert_ihex_checksums(records: &str) {
+	for record in records.lines() {
+		let sum = record_bytes(&record[1..]).iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+		assert_eq!(sum, 0, "{}", record);
+	}
+}
+
+/// The bytes of every S-record after the type, checksum included, add up to FF.
+fn assert_srec_checksums(records: &str) {
+	for record in records.lines() {
+		let sum = record_bytes(&record[2..]).iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+		assert_eq!(sum, 0xFF, "{}", record);
+	}
+}
+
+#[test]
+fn intel_hex_records() {
+	let records = assemble(&["-f", "ihex", "-o", "-"], DATA).unwrap();
+	assert_ihex_checksums(&records);
+	assert_eq!(records, "\
+:1000000000112233445566778899AABBCCDDEEFFF8
+:0400000500000000F7
+:00000001FF
+");
+}
+
+/// Data records only hold 16 address bits, so a record crossing 64K is split and the upper bits
+/// are set with an extended linear address record.
+#[test]
+fn intel_hex_splits_on_64k_boundaries() {
+	let records = assemble(&["-f", "ihex", "--load-address", "FFF8", "-o", "-"], DATA).unwrap();
+	assert_ihex_checksums(&records);
+	assert_eq!(records, "\
+:08FFF800001122334455667725
+:020000040001F9
+:080000008899AABBCCDDEEFFDC
+:040000050000FFF800
+:00000001FF
+");
+}
+
+#[test]
+fn s_records() {
+	let records = assemble(&["-f", "srec", "--load-address", "FFF0", "-o", "-"], DATA).unwrap();
+	assert_srec_checksums(&records);
+	assert_eq!(records, "\
+S007000050524F47C0
+S113FFF000112233445566778899AABBCCDDEEFF05
+S5030001FB
+S903FFF00D
+");
+}
+
+/// Images that don't fit in 64K use 24 bit addresses.
+#[test]
+fn s_records_past_64k() {
+	let records = assemble(&["-f", "srec", "--load-address", "FFF8", "-o", "-"], DATA).unwrap();
+	assert_srec_checksums(&records);
+	let types: Vec<&str> = records.lines().map(|record| &record[..2]).collect();
+	assert_eq!(types, vec!["S0", "S2", "S5", "S8"]);
+	assert!(records.contains("S21400FFF800112233445566778899AABBCCDDEEFF"), "{}", records);
+}
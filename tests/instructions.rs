@@ -0,0 +1,75 @@
+mod common;
+
+use std::fs;
+use std::path::PathBuf;
+
+use common::*;
+
+const EXTENSION: &str = "\
+# a made up SIC variant
+MOVE    F8  2    r1,r2
+HALT    FC  1    none   privileged
+STZ     E4  3/4  m      store
+LDZ     0C  3    m
+remove  LDA
+";
+
+/// Writes an instruction set extension to its own file, since the tests run in parallel.
+fn extension_file(name: &str, contents: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("sic_instructions_{}_{}.txt", std::process::id(), name));
+	fs::write(&path, contents).unwrap();
+	path
+}
+
+fn assemble_extended(name: &str, extension: &str, options: &[&str], source: &str) -> Result<String, String> {
+	let path = extension_file(name, extension);
+	let output = assemble(&[&["--instructions", path.to_str().unwrap()], options].concat(), source);
+	fs::remove_file(path).unwrap();
+	output
+}
+
+#[test]
+fn extensions_add_and_remove_instructions() {
+	let source = "\
+PROG    START   0
+FIRST   MOVE    A,X
+        STZ     ZERO
+        +STZ    ZERO
+        HALT
+ZERO    WORD    0
+        END     FIRST
+";
+	let object_program = assemble_extended("add", EXTENSION, &[], source).unwrap();
+	let code: Vec<&str> = object_program.lines().filter(|record| record.starts_with('T')).map(|record| &record[9..]).collect();
+	assert_eq!(code, vec!["F801", "E72005", "E710000A", "FC", "000000"]);
+	assert!(object_program.contains("M00000605+PROG"));
+
+	let errors = assemble_extended("remove", EXTENSION, &[], "PROG START 0\n LDA #0\n END\n").unwrap_err();
+	assert!(errors.contains("Error (line 2): Invalid line!"), "{}", errors);
+}
+
+#[test]
+fn extension_errors_name_the_file_line() {
+	let errors = assemble_extended("opcode", "LDY 0D 3 m\n", &[], "PROG START 0\n END\n").unwrap_err();
+	assert!(errors.contains("Error (line 1): Format 3 opcode 0D must be a multiple of 4!"), "{}", errors);
+
+	let errors = assemble_extended("signature", "# ok\nMOVE F8 2 m\n", &[], "PROG START 0\n END\n").unwrap_err();
+	assert!(errors.contains("Error (line 2):"), "{}", errors);
+
+	let errors = assemble_extended("unknown", "remove LDY\n", &[], "PROG START 0\n END\n").unwrap_err();
+	assert!(errors.contains("Can't remove unknown instruction LDY!"), "{}", errors);
+}
+
+/// Relaxation only promotes instructions that have a format 4.
+#[test]
+fn format_3_only_instructions_are_not_relaxed() {
+	let source = "\
+PROG    START   0
+HERE    LDZ     #0
+        RESB    5000
+        LDZ     HERE
+        END     HERE
+";
+	let errors = assemble_extended("relax", EXTENSION, &["--relax"], source).unwrap_err();
+	assert!(errors.contains("Error (line 4): HERE is out of range for format 3, and LDZ has no format 4!"), "{}", errors);
+}
@@ -0,0 +1,36 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+const SOURCE: &str = "\
+PROG    START   0
+FIRST   LDA     FAR
+.       comment
+HERE
+        RESB    5000
+FAR     WORD    1
+        J       HERE
+        END     FIRST
+";
+
+/// Pass 2 from the intermediate file sees the same lines as pass 2 straight after pass 1, including
+/// label-only lines and instructions promoted by relaxation.
+#[test]
+fn pass_2_from_intermediate_file_matches_direct_run() {
+	let directory = std::env::temp_dir().join(format!("sic_intermediate_{}", std::process::id()));
+	fs::create_dir_all(&directory).unwrap();
+	fs::write(directory.join("prog.sic"), SOURCE).unwrap();
+
+	stdout(run_in(&directory, &["--relax", "-i", "-l", "prog.sic"], ""));
+	let listing = fs::read_to_string(directory.join("prog.sic.lst")).unwrap();
+	let object_program = fs::read_to_string(directory.join("prog.sic.obj")).unwrap();
+	assert!(listing.contains("RELAX"));
+
+	stdout(run_in(&directory, &["--from-intermediate", "-l", "prog.sic.int"], ""));
+	assert_eq!(fs::read_to_string(directory.join("prog.sic.lst")).unwrap(), listing);
+	assert_eq!(fs::read_to_string(directory.join("prog.sic.obj")).unwrap(), object_program);
+
+	fs::remove_dir_all(&directory).unwrap();
+}
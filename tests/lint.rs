@@ -0,0 +1,28 @@
+mod common;
+
+use common::*;
+
+// lint exits non-zero when it finds problems, so only the report matters
+fn lint(source: &str) -> String {
+	String::from_utf8(run(&["lint", "-"], source).stdout).unwrap()
+}
+
+/// A subroutine called from several places, in any order, is checked and reported once.
+#[test]
+fn unsaved_return_address_reported_once() {
+	let source = "\
+PROG    START   0
+FIRST   JSUB    SUBA
+        JSUB    SUBB
+        JSUB    SUBA
+        J       FIRST
+SUBA    JSUB    SUBB
+        RSUB
+SUBB    RSUB
+        END     FIRST
+";
+	assert_eq!(lint(source), "\
+Warning (line 7): RSUB returns through L, which the JSUB on line 6 overwrote without saving it (STL) first.
+-: 1 problem(s) found
+");
+}
@@ -0,0 +1,184 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use serde_json::{json, Value};
+
+const URI: &str = "file:///prog.sic";
+const SOURCE: &str = "\
+PROG    START   1000
+FIRST   LDA     ALPHA
+.LOOP:  ADD     #1
+        J       .LOOP
+        RSUB
+ALPHA   WORD    5
+        END     FIRST
+";
+
+/// A scripted client talking JSON-RPC to the server over its stdin and stdout.
+struct Client {
+	server: Child,
+	output: BufReader<ChildStdout>,
+	next_id: i64,
+}
+
+impl Client {
+	fn start(args: &[&str]) -> Client {
+		let mut server = Command::new(env!("CARGO_BIN_EXE_sic_lsp"))
+			.args(args)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.spawn()
+			.unwrap();
+		let output = BufReader::new(server.stdout.take().unwrap());
+		let mut client = Client { server, output, next_id: 1 };
+		client.request("initialize", json!({}));
+		client
+	}
+
+	fn send(&mut self, message: Value) {
+		let body = message.to_string();
+		let input = self.server.stdin.as_mut().unwrap();
+		write!(input, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+		input.flush().unwrap();
+	}
+
+	fn receive(&mut self) -> Value {
+		let mut content_length = 0;
+		loop {
+			let mut header = String::new();
+			self.output.read_line(&mut header).unwrap();
+			match header.trim().strip_prefix("Content-Length:") {
+				Some(length) => content_length = length.trim().parse().unwrap(),
+				None if header.trim().is_empty() => break,
+				None => {}
+			}
+		}
+
+		let mut body = vec![0; content_length];
+		self.output.read_exact(&mut body).unwrap();
+		serde_json::from_slice(&body).unwrap()
+	}
+
+	fn request(&mut self, method: &str, params: Value) -> Value {
+		let id = self.next_id;
+		self.next_id += 1;
+		self.send(json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }));
+
+		let response = self.receive();
+		assert_eq!(response["id"], id);
+		response["result"].clone()
+	}
+
+	fn notify(&mut self, method: &str, params: Value) {
+		self.send(json!({ "jsonrpc": "2.0", "method": method, "params": params }));
+	}
+
+	/// Opens a document and returns the diagnostics published for it.
+	fn open(&mut self, text: &str) -> Value {
+		self.notify("textDocument/didOpen", json!({ "textDocument": { "uri": URI, "text": text } }));
+		self.receive()["params"]["diagnostics"].clone()
+	}
+
+	fn at(&mut self, method: &str, line: usize, character: usize) -> Value {
+		self.request(method, json!({
+			"textDocument": { "uri": URI },
+			"position": { "line": line, "character": character },
+			"context": { "includeDeclaration": true },
+		}))
+	}
+
+	fn stop(mut self) {
+		self.request("shutdown", Value::Null);
+		self.notify("exit", Value::Null);
+		assert!(self.server.wait().unwrap().success());
+	}
+}
+
+#[test]
+fn publishes_diagnostics_as_the_document_changes() {
+	let mut client = Client::start(&[]);
+	assert_eq!(client.open(SOURCE), json!([]));
+
+	client.notify("textDocument/didChange", json!({
+		"textDocument": { "uri": URI },
+		"contentChanges": [{ "text": "PROG START 0\n LDA FOO\n END\n" }],
+	}));
+	let diagnostics = client.receive()["params"]["diagnostics"].clone();
+	assert_eq!(diagnostics[0]["message"], "Undefined symbol FOO!");
+	assert_eq!(diagnostics[0]["severity"], 1);
+	assert_eq!(diagnostics[0]["range"]["start"]["line"], 1);
+
+	client.stop();
+}
+
+#[test]
+fn hover_shows_instruction_and_symbol_details() {
+	let mut client = Client::start(&[]);
+	client.open(SOURCE);
+
+	let instruction = client.at("textDocument/hover", 1, 9)["contents"]["value"].clone();
+	assert_eq!(instruction, "**LDA** opcode 00, format 3\n\nAddress 001000, pc-relative simple addressing\n\nObject code 032009");
+
+	let symbol = client.at("textDocument/hover", 1, 17)["contents"]["value"].clone();
+	assert_eq!(symbol, "**ALPHA** 00100C (absolute)\n\nDefined on line 6, length 3");
+
+	client.stop();
+}
+
+#[test]
+fn definition_and_references_follow_local_labels() {
+	let mut client = Client::start(&[]);
+	client.open(SOURCE);
+
+	let definition = client.at("textDocument/definition", 3, 18);
+	assert_eq!(definition["range"]["start"], json!({ "line": 2, "character": 0 }));
+
+	let references = client.at("textDocument/references", 5, 1);
+	let lines: Vec<Value> = references.as_array().unwrap().iter().map(|location| location["range"]["start"]["line"].clone()).collect();
+	assert_eq!(lines, vec![json!(1), json!(5)]);
+
+	client.stop();
+}
+
+#[test]
+fn completion_and_document_symbols() {
+	let mut client = Client::start(&[]);
+	client.open(SOURCE);
+
+	let completion = client.at("textDocument/completion", 4, 8);
+	let labels: Vec<&str> = completion.as_array().unwrap().iter().map(|item| item["label"].as_str().unwrap()).collect();
+	assert!(labels.contains(&"LDA") && labels.contains(&"RESW") && labels.contains(&"ALPHA"));
+	assert!(!labels.contains(&"FIRST.LOOP"));
+
+	let symbols = client.at("textDocument/documentSymbol", 0, 0);
+	let names: Vec<&str> = symbols.as_array().unwrap().iter().map(|symbol| symbol["name"].as_str().unwrap()).collect();
+	assert_eq!(names, vec!["PROG", "FIRST", "FIRST.LOOP", "ALPHA"]);
+	assert_eq!(symbols[1]["kind"], 12);
+	assert_eq!(symbols[3]["kind"], 13);
+
+	client.stop();
+}
+
+#[test]
+fn fixed_column_fields_that_touch() {
+	let mut client = Client::start(&["--long-names"]);
+	let source = "\
+PROG     START  1000
+LONGLABL+LDA    #ALPHA
+         J      LONGLABL
+ALPHA    WORD   5
+         END    LONGLABL
+";
+	assert_eq!(client.open(source), json!([]));
+
+	let instruction = client.at("textDocument/hover", 1, 10)["contents"]["value"].clone();
+	assert!(instruction.as_str().unwrap().starts_with("**LDA** opcode 00, format 4"), "{}", instruction);
+
+	let definition = client.at("textDocument/definition", 2, 17);
+	assert_eq!(definition["range"], json!({ "start": { "line": 1, "character": 0 }, "end": { "line": 1, "character": 8 } }));
+
+	let references = client.at("textDocument/references", 3, 2);
+	assert_eq!(references[0]["range"], json!({ "start": { "line": 1, "character": 17 }, "end": { "line": 1, "character": 22 } }));
+
+	client.stop();
+}
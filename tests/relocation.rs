@@ -0,0 +1,50 @@
+mod common;
+
+use common::*;
+
+/// A relocatable program can't address its own labels directly, because the 12 bit address field
+/// can't be relocated past 4096.
+const FAR_JUMP: &str = "\
+PROG    START   0
+HERE    LDA     #0
+        RESB    5000
+        J       HERE
+        END     HERE
+";
+
+#[test]
+fn direct_addressing_is_out_of_range_in_relocatable_programs() {
+	let errors = assemble(&["-o", "-"], FAR_JUMP).unwrap_err();
+	assert!(errors.contains("Error (line 4): HERE is out of range for format 3!"), "{}", errors);
+}
+
+#[test]
+fn relaxed_jumps_are_relocated_to_the_load_address() {
+	let output = run(&["--relax", "-f", "binary", "--load-address", "3000", "-o", "-", "-"], FAR_JUMP);
+	assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+	// +J HERE, with HERE moved from 0 to 3000
+	assert_eq!(output.stdout[5003..], [0x3F, 0x10, 0x30, 0x00]);
+}
+
+#[test]
+fn labels_are_relative_only_in_relocatable_programs() {
+	let relocatable = stdout(run(&["--relax", "-s", "-o", "-", "-"], FAR_JUMP));
+	assert!(relocatable.contains("HERE    000000  REL       2"), "{}", relocatable);
+
+	let absolute = stdout(run(&["--relax", "-s", "-o", "-", "-"], &FAR_JUMP.replace("START   0", "START   1000")));
+	assert!(absolute.contains("HERE    001000  ABS       2"), "{}", absolute);
+}
+
+/// SIC programs are loaded where they were assembled, so neither addresses nor WORD constants are relocated.
+#[test]
+fn sic_programs_have_no_modification_records() {
+	let source = "\
+PROG    START   0
+FIRST   LDA     F
+        RSUB
+F       WORD    F
+        END     FIRST
+";
+	let object_program = assemble(&["--sic"], source).unwrap();
+	assert_eq!(object_program, "HPROG  000000000009\nT00000003000006\nT000003034C0000\nT00000603000006\nE000000\n");
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use common::*;
+
+const PROGRAM: &str = "\
+COPY    START   0
+FIRST   LDB     #LENGTH
+        BASE    LENGTH
+        LDA     LENGTH
+        RSUB
+LENGTH  WORD    3
+        END     FIRST
+";
+
+/// Comments from the textbook whose second word is a mnemonic or directive are still comments.
+#[test]
+fn dot_comments_that_read_like_statements() {
+	let commented = "\
+COPY    START   0
+.READ AND WRITE A RECORD
+.SET BASE REGISTER
+FIRST   LDB     #LENGTH
+.BASE   LENGTH
+        BASE    LENGTH
+.LDA    LENGTH
+        LDA     LENGTH
+.       RSUB
+        RSUB
+LENGTH  WORD    3
+        END     FIRST
+";
+	assert_eq!(assemble(&[], commented), assemble(&[], PROGRAM));
+	assert!(assemble(&[], PROGRAM).is_ok());
+}
+
+#[test]
+fn local_labels_are_defined_with_a_colon() {
+	let source = "\
+PROG    START   0
+FIRST   LDA     #0
+.LOOP:  ADD     #1
+        J       .LOOP
+SECOND  LDA     #0
+.LOOP:
+        SUB     #1
+        J       .LOOP
+        END     FIRST
+";
+	let object_program = assemble(&[], source).unwrap();
+	let code: String = object_program.lines().filter(|record| record.starts_with('T')).map(|record| &record[9..]).collect();
+	// each J goes back to the .LOOP of its own subroutine
+	assert_eq!(code, "0100001900013F2FFA0100001D00013F2FFA");
+}
+
+/// Only the program name is written to object records, so other long names may share a prefix.
+#[test]
+fn long_names_sharing_a_prefix() {
+	let source = "\
+LONGPROGRAM START   0
+BUFFER_IN   LDA     BUFFER_OUT
+            RSUB
+BUFFER_OUT  WORD    1
+            END     BUFFER_IN
+";
+	let output = run(&["--long-names", "-"], source);
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+	assert!(String::from_utf8_lossy(&output.stdout).starts_with("HLONGPR"));
+}
+
+/// Fields that touch in fixed columns, with a line holding only a label, read the same as free-form.
+#[test]
+fn fixed_columns_are_detected() {
+	let fixed = "\
+COPY     START  0
+LONGLABL+LDA    #4096
+LOOP
+         J      LOOP          JUMP BACK
+         END    LONGLABL
+";
+	let free_form = "\
+COPY START 0
+LONGLABL +LDA #4096
+LOOP
+ J LOOP
+ END LONGLABL
+";
+	assert_eq!(assemble(&["--long-names"], fixed), assemble(&["--long-names"], free_form));
+	assert!(assemble(&["--long-names"], fixed).is_ok());
+}
+
+/// Space aligned free-form source with an operand past column 35 isn't mistaken for fixed columns.
+#[test]
+fn long_operands_are_free_form_unless_columns_are_forced() {
+	let source = "\
+PROG     START   0
+SRC      BYTE    C'THIS IS A LONGER TEST STRING'
+         END     SRC
+";
+	let object_program = assemble(&[], source).unwrap();
+	assert!(object_program.contains("544849532049532041204C4F4E47455220544553542053545249"), "{}", object_program);
+
+	let errors = assemble(&["--fixed-columns"], source).unwrap_err();
+	assert!(errors.contains("overflows columns 17-35"), "{}", errors);
+}
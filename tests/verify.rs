@@ -0,0 +1,71 @@
+mod common;
+
+use std::fs;
+
+use common::*;
+
+/// Writes an object program to its own file and runs verify on it, returning what it printed and
+/// whether the program passed.
+fn verify(name: &str, object_program: &str) -> (String, bool) {
+	let path = std::env::temp_dir().join(format!("sic_verify_{}_{}.obj", std::process::id(), name));
+	fs::write(&path, object_program).unwrap();
+	let output = run(&["verify", path.to_str().unwrap()], "");
+	fs::remove_file(path).unwrap();
+	(String::from_utf8(output.stdout).unwrap(), output.status.success())
+}
+
+#[test]
+fn assembled_programs_verify() {
+	let object_program = assemble_file(&[], "test.sicxe").unwrap();
+	let (report, ok) = verify("assembled", &object_program);
+	assert!(ok, "{}", report);
+	assert!(report.ends_with(": OK\n"));
+}
+
+#[test]
+fn record_problems() {
+	let object_program = "\
+HPROG  00000000000A
+T00000003000006
+T0000020300000
+T00000803000006X
+M00000005+PROG
+M00000905+PROG
+E000010
+";
+	let (report, ok) = verify("records", object_program);
+	assert!(!ok);
+	let lines: Vec<&str> = report.lines().collect();
+	let (summary, problems) = lines.split_last().unwrap();
+	assert_eq!(problems, [
+		"Error (line 3): Text record has an odd number of hex digits!",
+		"Error (line 3): Text record declares 3 bytes but contains 5 hex digits!",
+		"Error (line 4): Text record contains invalid hex!",
+		"Error (line 4): Text record has an odd number of hex digits!",
+		"Error (line 4): Text record declares 3 bytes but contains 7 hex digits!",
+		"Error (line 4): Text record 000008-00000B is outside of program range 000000-00000A!",
+		"Error (line 7): End address 000010 is outside of program range 000000-00000A!",
+		"Error (line 3): Text record overlaps text record on line 2!",
+		"Error (line 6): Modification record points at 00000B, which is not inside any text record!",
+	]);
+	assert!(summary.ends_with(": 9 problem(s) found"));
+}
+
+#[test]
+fn missing_and_misplaced_records() {
+	let (report, ok) = verify("order", "T00000003000006\nHPROG  000000000003\nE000000\nT00000003000006\n");
+	assert!(!ok);
+	assert!(report.contains("Error (line 2): Header record must be the first record!"), "{}", report);
+	assert!(report.contains("Error (line 4): Record found after end record!"), "{}", report);
+
+	let (report, _) = verify("empty", "");
+	assert!(report.contains("Error: No valid header record found!"), "{}", report);
+	assert!(report.contains("Error: No end record found!"), "{}", report);
+}
+
+#[test]
+fn missing_object_files_are_reported() {
+	let output = run(&["verify", "no_such_file.obj"], "");
+	assert!(!output.status.success());
+	assert!(String::from_utf8(output.stdout).unwrap().starts_with("Error: Could not open no_such_file.obj"));
+}